@@ -0,0 +1,143 @@
+//! Strategies of the computer controlled rival snakes
+
+use std::collections::{HashSet, VecDeque};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::game::{Coords, Dir, GameState, Snake, PLAYER};
+
+/// How the rival snakes choose where to go
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum Strategy {
+    /// Random direction, but not right into something
+    Random,
+    /// Straight to the closest food, but not right into something
+    #[default]
+    Greedy,
+    /// Shortest path to the food, only if there is enough space left after taking it
+    Safe,
+}
+
+/// Directions that are equally good for the `snake` to go in.
+/// Empty if every direction kills it.
+pub fn choose_dirs(game: &GameState, snake: &Snake, strategy: Strategy) -> Vec<Dir> {
+    let head = snake.body[0];
+    let safe: Vec<(Dir, Coords)> = Dir::ALL
+        .into_iter()
+        .filter(|&dir| dir != snake.dir.opposite())
+        .filter_map(|dir| Some((dir, game.neighbor(head, dir)?)))
+        .filter(|&(_, next)| !game.is_blocked(next))
+        .collect();
+
+    match strategy {
+        Strategy::Random => safe.into_iter().map(|(dir, _)| dir).collect(),
+        Strategy::Greedy => {
+            let food_distance = |c: Coords| {
+                game.food()
+                    .iter()
+                    .map(|f| f.pos.x.abs_diff(c.x) + f.pos.y.abs_diff(c.y))
+                    .min()
+                    .unwrap_or(0)
+            };
+            best_by(&safe, |next| usize::MAX - food_distance(next))
+        }
+        Strategy::Safe => {
+            let needed = snake.body.len();
+            let roomy: Vec<(Dir, Coords)> = safe
+                .iter()
+                .copied()
+                .filter(|&(_, next)| free_area(game, next, needed) >= needed)
+                .collect();
+
+            match path_to_food(game, &roomy) {
+                dirs if !dirs.is_empty() => dirs,
+                // no food can be reached safely, just survive as long as possible
+                _ => best_by(&safe, |next| free_area(game, next, usize::MAX)),
+            }
+        }
+    }
+}
+
+/// Turn of the player's snake steered by `strategy`, for games played by the computer.
+/// Ties are broken with `rng`.
+pub fn player_turn(game: &GameState, strategy: Strategy, rng: &mut impl Rng) -> Option<Dir> {
+    let snake = Snake {
+        owner: PLAYER,
+        body: game.snake_iter().collect(),
+        dir: game.snake_dir(),
+        score: game.score(),
+    };
+    let dirs = choose_dirs(game, &snake, strategy);
+    match dirs.len() {
+        0 => None,
+        n => Some(dirs[rng.gen_range(0..n)]).filter(|&dir| dir != snake.dir),
+    }
+}
+
+/// Directions of the moves with the highest score
+fn best_by(moves: &[(Dir, Coords)], score: impl Fn(Coords) -> usize) -> Vec<Dir> {
+    let scores: Vec<usize> = moves.iter().map(|&(_, next)| score(next)).collect();
+    let Some(&best) = scores.iter().max() else {
+        return Vec::new();
+    };
+    moves
+        .iter()
+        .zip(scores)
+        .filter(|&(_, s)| s == best)
+        .map(|(&(dir, _), _)| dir)
+        .collect()
+}
+
+/// First moves of the shortest paths to the closest food, going through the field
+/// with breadth first search, starting with `moves`
+fn path_to_food(game: &GameState, moves: &[(Dir, Coords)]) -> Vec<Dir> {
+    let food: HashSet<Coords> = game.food().iter().map(|f| f.pos).collect();
+    let mut visited: HashSet<Coords> = moves.iter().map(|&(_, next)| next).collect();
+    let mut queue: VecDeque<(Coords, Dir, usize)> =
+        moves.iter().map(|&(dir, next)| (next, dir, 1)).collect();
+
+    let mut found: Option<usize> = None;
+    let mut dirs = Vec::new();
+    while let Some((pos, first, distance)) = queue.pop_front() {
+        if found.is_some_and(|d| distance > d) {
+            break;
+        }
+        if food.contains(&pos) {
+            found = Some(distance);
+            if !dirs.contains(&first) {
+                dirs.push(first);
+            }
+            continue;
+        }
+        for dir in Dir::ALL {
+            let Some(next) = game.neighbor(pos, dir) else {
+                continue;
+            };
+            if !game.is_blocked(next) && visited.insert(next) {
+                queue.push_back((next, first, distance + 1));
+            }
+        }
+    }
+    dirs
+}
+
+/// Number of free cells that can be reached from `start`, counting up to `limit`
+fn free_area(game: &GameState, start: Coords, limit: usize) -> usize {
+    let mut visited = HashSet::from([start]);
+    let mut queue = VecDeque::from([start]);
+    while let Some(pos) = queue.pop_front() {
+        if visited.len() >= limit {
+            break;
+        }
+        for dir in Dir::ALL {
+            let Some(next) = game.neighbor(pos, dir) else {
+                continue;
+            };
+            if !game.is_blocked(next) && visited.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+    visited.len().min(limit)
+}
@@ -1,8 +1,18 @@
-use clap::Parser;
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use eyre::Context;
 use rand::random;
 
-use crate::{game::GameConf, themes};
+use crate::{
+    ai::Strategy,
+    bench::SeedRange,
+    code::GameCode,
+    game::{FoodMovement, FoodPlacement, GameConf, Goal, SelfHit, SpeedCurve, MAX_FIELD_SIZE},
+    render::{self, Layout},
+    stats::ThemeNames,
+    themes,
+};
 
 /// Highly customizable, cross-platform, and blazingly fast terminal snake game
 #[derive(Parser, Debug)]
@@ -20,6 +30,84 @@ pub struct SnekGameCli {
     #[arg(long, default_value_t = 0)]
     seed: u64,
 
+    /// Number of times a second the screen is redrawn
+    #[arg(long, default_value_t = 60, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub fps: u32,
+
+    /// After the game, report latency between pressing a key and the snake turning
+    #[arg(long)]
+    pub measure_latency: bool,
+
+    /// The game goes FACTOR times faster while Space is held
+    #[arg(long, default_value_t = 2.0, value_name = "FACTOR", value_parser = speed_factor)]
+    pub boost: f64,
+
+    /// The game goes FACTOR times slower while B is held. No brake by default.
+    #[arg(long, value_name = "FACTOR", value_parser = speed_factor)]
+    pub brake: Option<f64>,
+
+    /// Play the replay from FILE instead of a new game. Game config options are ignored.
+    #[arg(long, value_name = "FILE")]
+    pub replay: Option<PathBuf>,
+
+    /// Save the replay of the game into FILE
+    #[arg(long, value_name = "FILE")]
+    pub save_replay: Option<PathBuf>,
+
+    /// Record the game into FILE in asciicast v2 format.
+    /// With --replay, the replay is exported without playing it in the terminal.
+    #[arg(long, value_name = "FILE")]
+    pub asciicast: Option<PathBuf>,
+
+    /// Export the game into FILE as .svg or .html instead of playing it.
+    /// With --replay, the whole replay is exported as an animation.
+    #[arg(long, value_name = "FILE")]
+    pub export: Option<PathBuf>,
+
+    /// Export only the frame at TICK. 0 is the start of the game.
+    #[arg(long, value_name = "TICK", requires = "export")]
+    pub export_tick: Option<u64>,
+
+    /// Start the game from a game code, which is printed after every game.
+    /// Game config and theme options are ignored.
+    #[arg(long, value_name = "CODE", conflicts_with_all = ["replay", "campaign", "daily"])]
+    pub code: Option<String>,
+
+    /// Play the daily challenge, which is the same for everyone on the same UTC date.
+    /// Only the first attempt of the day is scored. Game config options are ignored.
+    #[arg(long, conflicts_with_all = ["replay", "export", "campaign"])]
+    pub daily: bool,
+
+    /// Play the campaign: a sequence of levels with goals, continuing from the last unfinished level.
+    /// Game config options are ignored.
+    #[arg(long, conflicts_with_all = ["replay", "export"])]
+    pub campaign: bool,
+
+    /// Play the campaign from level N, it must be unlocked
+    #[arg(long, value_name = "N", requires = "campaign")]
+    pub level: Option<usize>,
+
+    /// Let the bot started with PROGRAM play instead of the player.
+    /// PROGRAM is split into the program and its arguments on spaces, which can be quoted.
+    #[arg(long, value_name = "PROGRAM", conflicts_with_all = ["replay", "campaign", "daily"])]
+    pub bot_cmd: Option<String>,
+
+    /// Practice: press R or Backspace to rewind the game a few ticks back, also after dying.
+    /// Games with rewinds are unranked.
+    #[arg(long, conflicts_with_all = ["replay", "campaign", "daily", "bot_cmd"])]
+    pub practice: bool,
+
+    /// Number of ticks every rewind goes back in practice
+    #[arg(long, default_value_t = 12, value_name = "N", requires = "practice")]
+    pub rewind_ticks: usize,
+
+    /// Time a bot has to make every move, in milliseconds
+    #[arg(long, default_value_t = 100, value_name = "MS", global = true)]
+    pub bot_timeout: u64,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     // =#= Game config:
     /// Width of the field
     #[arg(long, default_value_t = 25, help_heading = "Game config")]
@@ -31,12 +119,98 @@ pub struct SnekGameCli {
 
     /// Make field the size of the terminal window
     #[arg(long, help_heading = "Game config")]
-    fullscreen: bool,
+    pub fullscreen: bool,
 
     /// Makes the walls solid
     #[arg(long, short = 'w', help_heading = "Game config")]
     walls: bool,
 
+    /// Number of portal pairs placed randomly on the field.
+    /// Entering a portal moves the snake to the other portal of the pair.
+    #[arg(
+        long,
+        short = 'p',
+        default_value_t = 0,
+        value_name = "N",
+        help_heading = "Game config"
+    )]
+    portals: u32,
+
+    /// What happens when the snake hits itself
+    #[arg(long, value_enum, default_value_t = SelfHit::Die, value_name = "RULE", help_heading = "Game config")]
+    on_self_hit: SelfHit,
+
+    /// Number of lives. After losing a life the snake respawns
+    /// in a safe place and keeps the score
+    #[arg(
+        long,
+        default_value_t = 1,
+        value_name = "N",
+        help_heading = "Game config"
+    )]
+    lives: u32,
+
+    /// Win the game by reaching score N
+    #[arg(long, value_name = "N", group = "goal", help_heading = "Game config")]
+    goal_score: Option<u32>,
+
+    /// Win the game by staying alive for SECS seconds
+    #[arg(
+        long,
+        value_name = "SECS",
+        group = "goal",
+        help_heading = "Game config"
+    )]
+    survive: Option<u32>,
+
+    /// Time attack: get the highest score before SECS seconds run out
+    #[arg(
+        long,
+        value_name = "SECS",
+        group = "goal",
+        help_heading = "Game config"
+    )]
+    time_limit: Option<u32>,
+
+    /// Where new food is placed
+    #[arg(long, value_enum, default_value_t = FoodPlacement::Uniform, value_name = "POLICY", help_heading = "Game config")]
+    food_placement: FoodPlacement,
+
+    /// Number of food items that move
+    #[arg(
+        long,
+        default_value_t = 0,
+        value_name = "N",
+        help_heading = "Game config"
+    )]
+    moving_food: u32,
+
+    /// How the moving food moves
+    #[arg(long, value_enum, default_value_t = FoodMovement::Random, help_heading = "Game config")]
+    food_movement: FoodMovement,
+
+    /// Moving food moves once in N steps of the snake
+    #[arg(
+        long,
+        default_value_t = 2,
+        value_name = "N",
+        help_heading = "Game config"
+    )]
+    food_move_every: u32,
+
+    /// Number of computer controlled snakes competing for the food
+    #[arg(
+        long,
+        default_value_t = 0,
+        value_name = "N",
+        help_heading = "Game config"
+    )]
+    rivals: u32,
+
+    /// How the rival snakes choose where to go
+    #[arg(long, value_enum, default_value_t = Strategy::Greedy, help_heading = "Game config")]
+    rival_strategy: Strategy,
+
     /// Initial length of the snake
     #[arg(
         long,
@@ -51,15 +225,36 @@ pub struct SnekGameCli {
     #[arg(long, short = 'f', default_value_t = 1, help_heading = "Game config")]
     food: u32,
 
-    /// Initial speed. Snake will move N times a second
+    /// Initial speed. Snake will move N times a second, N can be fractional,
+    /// from 0.01 to 1000
     #[arg(
         long,
         short = 's',
-        default_value_t = 6,
+        default_value_t = 6.0,
         value_name = "N",
         help_heading = "Game config"
     )]
-    speed: u32,
+    speed: f64,
+
+    /// How the speed grows with the score
+    #[arg(long, value_enum, default_value_t = SpeedCurve::Linear, help_heading = "Game config")]
+    speed_curve: SpeedCurve,
+
+    /// Speeds of the stepped curve, e.g. 0:4,10:6.5,25:9. From each score
+    /// the snake moves at the speed after it.
+    #[arg(
+        long,
+        value_name = "SCORE:SPEED",
+        value_delimiter = ',',
+        value_parser = speed_step,
+        required_if_eq("speed_curve", "stepped"),
+        help_heading = "Game config"
+    )]
+    speed_steps: Vec<(u32, f64)>,
+
+    /// The snake never moves faster than N times a second
+    #[arg(long, value_name = "N", help_heading = "Game config")]
+    max_speed: Option<f64>,
 
     /// Food needed to increece speed
     #[arg(
@@ -83,34 +278,87 @@ pub struct SnekGameCli {
     /// Food theme
     #[arg(long, value_enum, default_value_t = themes::FoodBuiltin::Emoji, help_heading = "Themes")]
     food_theme: themes::FoodBuiltin,
+
+    /// Ask the terminal how wide it draws emoji, and use the ascii food theme
+    /// instead of emoji if they are narrow. Some terminals don't answer, which delays the start.
+    #[arg(long, help_heading = "Themes")]
+    probe_emoji_width: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Play many games with built-in strategies or bots without the terminal, and compare them.
+    /// The game config options given before the command are used for all games.
+    Bench(BenchArgs),
+    /// Show the lifetime stats of the games played on this machine
+    Stats,
+}
+
+#[derive(Args, Debug)]
+pub struct BenchArgs {
+    /// Built-in strategy steering the snake, can be given many times.
+    /// Without strategies and bots, all built-in strategies are compared.
+    #[arg(long = "strategy", value_enum, value_name = "STRATEGY")]
+    pub strategies: Vec<Strategy>,
+
+    /// Command starting a bot, can be given many times
+    #[arg(long = "bot", value_name = "PROGRAM")]
+    pub bots: Vec<String>,
+
+    /// Number of games every strategy and bot plays
+    #[arg(long, default_value_t = 100, value_name = "N")]
+    pub games: u64,
+
+    /// Seeds of the games, START.. or START..END with END excluded
+    #[arg(long, default_value = "1..", value_name = "START..END")]
+    pub seed_range: SeedRange,
+
+    /// Number of threads playing the games, by default one for every CPU
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    pub threads: Option<u16>,
+
+    /// Games still going after this many steps are stopped
+    #[arg(long, default_value_t = 10_000, value_name = "N")]
+    pub max_ticks: u64,
+
+    /// Print the results as JSON instead of a table
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// Parses the factor of the boost or the brake, which must be at least 1
+fn speed_factor(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(f) if f >= 1.0 && f.is_finite() => Ok(f),
+        Ok(_) => Err("the factor must be at least 1".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Parses a step of the stepped speed curve, `SCORE:SPEED`
+fn speed_step(s: &str) -> Result<(u32, f64), String> {
+    let (score, speed) = s.split_once(':').ok_or("expected SCORE:SPEED")?;
+    let score = score.parse().map_err(|e| format!("{score:?}: {e}"))?;
+    let speed = speed.parse().map_err(|e| format!("{speed:?}: {e}"))?;
+    Ok((score, speed))
 }
 
 /// Validates and creates the game config from cli arguments
-pub fn create_game_conf(a: &SnekGameCli) -> eyre::Result<GameConf> {
+pub fn create_game_conf(a: &SnekGameCli, theme: &themes::FullTheme) -> eyre::Result<GameConf> {
     let w: usize;
     let h: usize;
 
     if a.fullscreen {
-        let win = crossterm::terminal::window_size()
+        let (columns, rows) = crossterm::terminal::size()
             .wrap_err("Unable to get the size of the terminal window")?;
-        w = ((win.columns - 2) / 2) as usize;
-        h = (win.rows - 2) as usize;
+        let (max_w, max_h) = Layout::new(theme).max_field_size(columns as usize, rows as usize);
+        (w, h) = (max_w.min(MAX_FIELD_SIZE), max_h.min(MAX_FIELD_SIZE));
     } else {
         w = a.width;
         h = a.height;
     }
 
-    match () {
-        _ if h == 0 => eyre::bail!("Height cannot be zero"),
-        _ if w == 0 => eyre::bail!("Width cannot be zero"),
-        _ if a.speed == 0 => eyre::bail!("Speed cannot be zero"), // TODO: when snake speed is 0 make the snake move only on input
-        _ if a.snake_length == 0 => eyre::bail!("Snake length cannot be zero"),
-        _ if a.snake_length > w => {
-            eyre::bail!("Initial snake length cannot be larger then the width of the field")
-        }
-        _ => (),
-    }
-    Ok(GameConf {
+    let conf = GameConf {
         food_to_speed_up: a.food_to_speed_up,
         food_n: a.food,
         initial_speed: a.speed,
@@ -122,14 +370,85 @@ pub fn create_game_conf(a: &SnekGameCli) -> eyre::Result<GameConf> {
             seed => seed,
         },
         solid_walls: a.walls,
-    })
+        portals: a.portals,
+        on_self_hit: a.on_self_hit,
+        lives: a.lives,
+        obstacles: Vec::new(),
+        goal: match (a.goal_score, a.survive, a.time_limit) {
+            (Some(n), _, _) => Some(Goal::Score(n)),
+            (_, Some(secs), _) => Some(Goal::Survive(secs)),
+            (_, _, Some(secs)) => Some(Goal::TimeAttack(secs)),
+            _ => None,
+        },
+        food_placement: a.food_placement,
+        moving_food: a.moving_food,
+        food_movement: a.food_movement,
+        food_move_every: a.food_move_every,
+        rivals: a.rivals,
+        rival_strategy: a.rival_strategy,
+        speed_curve: a.speed_curve,
+        speed_steps: {
+            let mut steps = a.speed_steps.clone();
+            steps.sort_by_key(|&(score, _)| score);
+            steps
+        },
+        max_speed: a.max_speed,
+    };
+    conf.validate()?;
+    Ok(conf)
 }
 
-pub fn into_theme(args: SnekGameCli) -> themes::FullTheme {
-    themes::FullTheme {
-        board: args.board_theme.into(),
-        snake: args.snake_theme.into(),
-        food: args.food_theme.into(),
+impl SnekGameCli {
+    /// Replaces theme options with the themes of the game code
+    pub fn use_code_themes(&mut self, code: &GameCode) {
+        self.snake_theme = code.snake_theme.clone();
+        self.board_theme = code.board_theme.clone();
+        self.food_theme = code.food_theme.clone();
+    }
+
+    /// Names of the chosen themes, for the lifetime stats
+    pub fn theme_names(&self) -> ThemeNames {
+        let name = |v: Option<clap::builder::PossibleValue>| v.unwrap().get_name().to_string();
+        ThemeNames {
+            snake: name(self.snake_theme.to_possible_value()),
+            board: name(self.board_theme.to_possible_value()),
+            food: name(self.food_theme.to_possible_value()),
+        }
+    }
+
+    /// Code of the game with `conf` and the chosen themes
+    pub fn game_code(&self, conf: GameConf) -> GameCode {
+        GameCode {
+            conf,
+            snake_theme: self.snake_theme.clone(),
+            board_theme: self.board_theme.clone(),
+            food_theme: self.food_theme.clone(),
+        }
+    }
+}
+
+/// Creates the theme from cli arguments and checks that it can be displayed
+pub fn into_theme(args: &SnekGameCli) -> eyre::Result<themes::FullTheme> {
+    // emoji food would skew the board if the terminal draws emoji narrow
+    let food = match args.food_theme {
+        themes::FoodBuiltin::Emoji if !render::emoji_is_wide(args.probe_emoji_width) => {
+            themes::FoodBuiltin::Ascii
+        }
+        ref food => food.clone(),
+    };
+
+    let theme = themes::FullTheme {
+        board: args.board_theme.clone().into(),
+        snake: args.snake_theme.clone().into(),
+        food: food.into(),
         display_score: !args.hide_score,
+    };
+
+    let bad_glyphs = theme.validate();
+    if !bad_glyphs.is_empty() {
+        let list: Vec<String> = bad_glyphs.iter().map(|g| g.to_string()).collect();
+        eyre::bail!("Invalid theme:\n{}", list.join("\n"));
     }
+
+    Ok(theme)
 }
@@ -0,0 +1,132 @@
+//! Recording of the game in the asciicast v2 format, which can be played with asciinema.
+//! See: <https://docs.asciinema.org/manual/asciicast/v2/>
+
+use std::{
+    io::Write,
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{
+    game::{GameState, GameStatus},
+    render::{AnsiBackend, Layout, Renderer},
+    replay::Replay,
+    themes::FullTheme,
+};
+
+enum Clock {
+    /// Time is measured as it goes
+    Real(Instant),
+    /// Time is advanced manually, when recording without a terminal
+    Virtual(Duration),
+}
+
+#[derive(Serialize)]
+struct Header {
+    version: u32,
+    width: u16,
+    height: u16,
+    timestamp: u64,
+}
+
+pub struct Asciicast {
+    out: Box<dyn Write>,
+    clock: Clock,
+}
+
+impl Asciicast {
+    /// Starts the recording, writing the header with the size of the terminal
+    pub fn new(mut out: Box<dyn Write>, columns: u16, rows: u16) -> std::io::Result<Self> {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let header = Header {
+            version: 2,
+            width: columns,
+            height: rows,
+            timestamp,
+        };
+        serde_json::to_writer(&mut out, &header)?;
+        writeln!(out)?;
+
+        Ok(Self {
+            out,
+            clock: Clock::Real(Instant::now()),
+        })
+    }
+
+    /// Makes the recording use virtual time, that is advanced with [`Self::advance`]
+    pub fn with_virtual_time(mut self) -> Self {
+        self.clock = Clock::Virtual(Duration::ZERO);
+        self
+    }
+
+    pub fn advance(&mut self, dt: Duration) {
+        if let Clock::Virtual(time) = &mut self.clock {
+            *time += dt;
+        }
+    }
+
+    fn time(&self) -> f64 {
+        match self.clock {
+            Clock::Real(start) => start.elapsed().as_secs_f64(),
+            Clock::Virtual(time) => time.as_secs_f64(),
+        }
+    }
+
+    fn event(&mut self, code: &str, data: &str) -> std::io::Result<()> {
+        let event = json!([self.time(), code, data]);
+        writeln!(self.out, "{event}")
+    }
+
+    /// Records bytes written to the terminal
+    pub fn output(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.event("o", &String::from_utf8_lossy(bytes))
+    }
+
+    /// Writes out everything recorded, the recording must not be used after that
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+
+    /// Records resize of the terminal
+    pub fn resize(&mut self, columns: u16, rows: u16) -> std::io::Result<()> {
+        self.event("r", &format!("{columns}x{rows}"))
+    }
+}
+
+/// Plays the replay without a terminal, recording it into `out`
+pub fn export_replay(replay: &Replay, theme: &FullTheme, out: Box<dyn Write>) -> eyre::Result<()> {
+    let mut game = GameState::new(replay.conf.clone());
+    let mut turns = replay.player();
+
+    let mut renderer = Renderer::new(AnsiBackend::headless(), game.width(), game.height(), theme);
+    let (columns, rows) = renderer.screen_size();
+    let recording = Asciicast::new(out, columns, rows)?.with_virtual_time();
+    renderer.backend_mut().record(recording);
+
+    while game.tick() < replay.ticks {
+        if let Some((w, h)) = turns.resize_at(game.tick()) {
+            game.resize_field(w, h);
+            let (columns, rows) = Layout::new(theme).screen_size(w, h);
+            renderer.resize(columns as u16, rows as u16)?;
+        }
+        game.make_step(turns.turn_at(game.tick()));
+
+        renderer.render_game(&game, theme)?;
+        renderer
+            .backend_mut()
+            .advance_recording(game.expected_frametime());
+
+        if let GameStatus::Dead | GameStatus::Win = game.status() {
+            break;
+        }
+    }
+
+    renderer.backend_mut().finish_recording()?;
+    Ok(())
+}
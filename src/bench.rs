@@ -0,0 +1,346 @@
+//! Benchmark of the strategies steering the snake: many games played without the terminal,
+//! with the results summed up
+
+use std::{collections::BTreeMap, str::FromStr, thread, time::Duration};
+
+use clap::ValueEnum;
+use rand::{rngs::StdRng, SeedableRng};
+use serde::Serialize;
+
+use crate::{
+    ai::{self, Strategy},
+    args::BenchArgs,
+    bot::{self, Bot},
+    game::{DeathCause, GameConf, GameState, GameStatus},
+};
+
+/// Who steers the snake
+#[derive(Clone, Debug)]
+pub enum Player {
+    Strategy(Strategy),
+    /// Command starting the bot
+    Bot(String),
+}
+
+impl Player {
+    pub fn name(&self) -> String {
+        match self {
+            Player::Strategy(s) => s.to_possible_value().unwrap().get_name().to_string(),
+            Player::Bot(cmd) => cmd.clone(),
+        }
+    }
+}
+
+/// Seeds `start`, `start + 1`, ... up to `end`, which is excluded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SeedRange {
+    pub start: u64,
+    pub end: Option<u64>,
+}
+
+impl FromStr for SeedRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once("..")
+            .ok_or("expected a range START..END or START..")?;
+        let parse = |n: &str| n.parse::<u64>().map_err(|e| format!("{n:?}: {e}"));
+        let range = Self {
+            start: parse(start)?,
+            end: match end {
+                "" => None,
+                end => Some(parse(end)?),
+            },
+        };
+        match range.end {
+            Some(end) if end <= range.start => Err("the range is empty".to_string()),
+            _ => Ok(range),
+        }
+    }
+}
+
+impl SeedRange {
+    /// The first `n` seeds of the range
+    pub fn take(self, n: u64) -> eyre::Result<Vec<u64>> {
+        let end = self.start.saturating_add(n);
+        match self.end {
+            Some(last) if last < end => eyre::bail!(
+                "Seed range {}..{last} has only {} seeds, but {n} games are to be played",
+                self.start,
+                last - self.start
+            ),
+            _ => Ok((self.start..end).collect()),
+        }
+    }
+}
+
+/// How a game ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ending {
+    Won,
+    Died(DeathCause),
+    /// The bot forfeited
+    Forfeit,
+    /// The game was stopped after the maximum number of steps
+    Unfinished,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GameResult {
+    pub score: u32,
+    pub ticks: u64,
+    pub food_eaten: u32,
+    pub ending: Ending,
+}
+
+/// Plays the game with `player` until it ends, or `max_ticks` pass
+pub fn play_game(
+    player: &Player,
+    conf: GameConf,
+    bot_timeout: Duration,
+    max_ticks: u64,
+) -> eyre::Result<GameResult> {
+    let mut forfeit = false;
+    let game = match player {
+        Player::Strategy(strategy) => {
+            // ties are broken with the seed of the game, so the results can be reproduced
+            let mut rng = StdRng::seed_from_u64(conf.seed);
+            let mut game = GameState::new(conf);
+            while game.tick() < max_ticks {
+                let turn = ai::player_turn(&game, *strategy, &mut rng)
+                    .filter(|_| game.status() == GameStatus::Ongoing);
+                game.make_step(turn);
+                if let GameStatus::Dead | GameStatus::Win = game.status() {
+                    break;
+                }
+            }
+            game
+        }
+        Player::Bot(cmd) => {
+            let mut bot = Bot::spawn(cmd, bot_timeout)?;
+            let (game, _) = bot::play(GameState::new(conf), &mut bot, max_ticks);
+            forfeit = bot.forfeit().is_some();
+            game
+        }
+    };
+
+    let ending = match (game.status(), game.last_death()) {
+        (GameStatus::Win, _) => Ending::Won,
+        (GameStatus::Dead, Some(death)) => Ending::Died(death.cause),
+        _ if forfeit => Ending::Forfeit,
+        _ => Ending::Unfinished,
+    };
+    Ok(GameResult {
+        score: game.score(),
+        ticks: game.tick(),
+        food_eaten: game.food_eaten(),
+        ending,
+    })
+}
+
+/// Results of all games of a player
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Summary {
+    pub player: String,
+    pub games: usize,
+    pub mean_score: f64,
+    pub median_score: f64,
+    pub win_rate: f64,
+    /// Steps made for every food eaten, none if no food was eaten
+    pub steps_per_food: Option<f64>,
+    /// Number of games lost to every cause
+    pub deaths: BTreeMap<&'static str, usize>,
+    pub forfeits: usize,
+    /// Games stopped after the maximum number of steps
+    pub unfinished: usize,
+}
+
+impl Summary {
+    pub fn new(player: String, results: &[GameResult]) -> Self {
+        let n = results.len();
+        let mut scores: Vec<u32> = results.iter().map(|r| r.score).collect();
+        scores.sort_unstable();
+        let median_score = match n {
+            0 => 0.0,
+            n if n % 2 == 0 => f64::from(scores[n / 2 - 1] + scores[n / 2]) / 2.0,
+            n => f64::from(scores[n / 2]),
+        };
+        let rate = |count: usize| match n {
+            0 => 0.0,
+            n => count as f64 / n as f64,
+        };
+        let count = |ending: Ending| results.iter().filter(|r| r.ending == ending).count();
+
+        let ticks: u64 = results.iter().map(|r| r.ticks).sum();
+        let food: u64 = results.iter().map(|r| u64::from(r.food_eaten)).sum();
+
+        let mut deaths = BTreeMap::new();
+        for r in results {
+            if let Ending::Died(cause) = r.ending {
+                *deaths.entry(cause.kind()).or_insert(0) += 1;
+            }
+        }
+
+        Self {
+            player,
+            games: n,
+            mean_score: rate(scores.iter().map(|&s| s as usize).sum()),
+            median_score,
+            win_rate: rate(count(Ending::Won)),
+            steps_per_food: (food > 0).then(|| ticks as f64 / food as f64),
+            deaths,
+            forfeits: count(Ending::Forfeit),
+            unfinished: count(Ending::Unfinished),
+        }
+    }
+}
+
+/// Plays a game with `player` on every seed, on `threads` threads. The results don't
+/// depend on the number of threads, every game is determined by its seed.
+pub fn run(
+    player: &Player,
+    conf: &GameConf,
+    seeds: &[u64],
+    threads: usize,
+    bot_timeout: Duration,
+    max_ticks: u64,
+) -> eyre::Result<Summary> {
+    let threads = threads.clamp(1, seeds.len().max(1));
+    let chunk = seeds.len().div_ceil(threads).max(1);
+
+    let results = thread::scope(|scope| {
+        let handles: Vec<_> = seeds
+            .chunks(chunk)
+            .map(|seeds| {
+                scope.spawn(move || {
+                    seeds
+                        .iter()
+                        .map(|&seed| {
+                            let conf = GameConf {
+                                seed,
+                                ..conf.clone()
+                            };
+                            play_game(player, conf, bot_timeout, max_ticks)
+                        })
+                        .collect::<eyre::Result<Vec<_>>>()
+                })
+            })
+            .collect();
+
+        // joined in order, so the results are in the order of the seeds
+        let mut results = Vec::with_capacity(seeds.len());
+        for handle in handles {
+            results.extend(handle.join().expect("benchmark thread panicked")?);
+        }
+        eyre::Ok(results)
+    })?;
+
+    Ok(Summary::new(player.name(), &results))
+}
+
+/// Runs the benchmark of the `bench` command with games of `conf`, and prints the results
+pub fn run_command(args: &BenchArgs, conf: &GameConf, bot_timeout: Duration) -> eyre::Result<()> {
+    let seeds = args.seed_range.take(args.games)?;
+    let threads = match args.threads {
+        Some(n) => usize::from(n),
+        None => thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+
+    let mut players: Vec<Player> = args
+        .strategies
+        .iter()
+        .map(|&s| Player::Strategy(s))
+        .chain(args.bots.iter().map(|cmd| Player::Bot(cmd.clone())))
+        .collect();
+    if players.is_empty() {
+        players = Strategy::value_variants()
+            .iter()
+            .map(|&s| Player::Strategy(s))
+            .collect();
+    }
+
+    let summaries = players
+        .iter()
+        .map(|p| run(p, conf, &seeds, threads, bot_timeout, args.max_ticks))
+        .collect::<eyre::Result<Vec<_>>>()?;
+    match args.json {
+        true => println!("{}", serde_json::to_string_pretty(&summaries)?),
+        false => print_table(&summaries),
+    }
+    Ok(())
+}
+
+/// Prints the summaries as a table
+pub fn print_table(summaries: &[Summary]) {
+    let width = summaries
+        .iter()
+        .map(|s| s.player.len())
+        .chain([6])
+        .max()
+        .unwrap_or(0);
+
+    println!(
+        "{:<width$}  {:>6}  {:>8}  {:>8}  {:>6}  {:>10}  {:>8}  {:>10}  Deaths",
+        "Player", "Games", "Mean", "Median", "Wins", "Steps/food", "Forfeits", "Unfinished"
+    );
+    for s in summaries {
+        let steps_per_food = match s.steps_per_food {
+            Some(steps) => format!("{steps:.1}"),
+            None => "-".to_string(),
+        };
+        let deaths: Vec<String> = s
+            .deaths
+            .iter()
+            .map(|(cause, n)| format!("{cause} {n}"))
+            .collect();
+        println!(
+            "{:<width$}  {:>6}  {:>8.2}  {:>8.1}  {:>5.1}%  {:>10}  {:>8}  {:>10}  {}",
+            s.player,
+            s.games,
+            s.mean_score,
+            s.median_score,
+            s.win_rate * 100.0,
+            steps_per_food,
+            s.forfeits,
+            s.unfinished,
+            deaths.join(", "),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seed_ranges() {
+        let range = |s: &str| s.parse::<SeedRange>();
+        assert_eq!(
+            range("5.."),
+            Ok(SeedRange {
+                start: 5,
+                end: None
+            })
+        );
+        assert_eq!(range("5..10").unwrap().take(5).unwrap(), [5, 6, 7, 8, 9]);
+        assert!(range("5..10").unwrap().take(6).is_err());
+        assert!(range("5..5").is_err());
+        assert!(range("5").is_err());
+    }
+
+    #[test]
+    fn results_do_not_depend_on_threads() {
+        let conf = crate::game::test_conf();
+        let seeds: Vec<u64> = (1..=12).collect();
+        let bench = |threads| {
+            let player = Player::Strategy(Strategy::Greedy);
+            run(&player, &conf, &seeds, threads, Duration::ZERO, 2000).unwrap()
+        };
+
+        let summary = bench(1);
+        assert_eq!(summary.games, 12);
+        assert!(summary.mean_score > 0.0);
+        assert_eq!(summary, bench(5));
+    }
+}
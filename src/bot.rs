@@ -0,0 +1,344 @@
+//! External bots: programs that play the game over stdin and stdout.
+//!
+//! Every tick the bot gets the state of the game as one line of JSON, see [`BotState`],
+//! and answers with one line of JSON with its move, e.g. `{"move": "up"}`.
+//! The move is one of `up`, `down`, `left`, `right` and `straight`.
+//! A bot that answers too late, answers with something else, answers more than once
+//! or exits forfeits the game. What the bot writes to stderr is ignored.
+
+use std::{
+    io::{BufRead, BufReader, BufWriter, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError},
+    thread,
+    time::Duration,
+};
+
+use eyre::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::{Coords, Dir, GameState, GameStatus},
+    replay::Replay,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Move {
+    Up,
+    Down,
+    Left,
+    Right,
+    Straight,
+}
+
+impl From<Dir> for Move {
+    fn from(dir: Dir) -> Self {
+        match dir {
+            Dir::Up => Move::Up,
+            Dir::Down => Move::Down,
+            Dir::Left => Move::Left,
+            Dir::Right => Move::Right,
+        }
+    }
+}
+
+impl Move {
+    /// Turn of the snake going in `dir`, turning back is the same as going straight
+    pub fn turn(self, dir: Dir) -> Option<Dir> {
+        let to = match self {
+            Move::Up => Dir::Up,
+            Move::Down => Dir::Down,
+            Move::Left => Dir::Left,
+            Move::Right => Dir::Right,
+            Move::Straight => return None,
+        };
+        to.is_perpendicular(dir).then_some(to)
+    }
+}
+
+/// What the bot is sent every tick
+#[derive(Debug, Serialize)]
+pub struct BotState<'a> {
+    pub tick: u64,
+    pub width: usize,
+    pub height: usize,
+    /// Whether hitting the walls kills, otherwise the snake goes through them
+    pub solid_walls: bool,
+    pub dir: Move,
+    /// From the head to the tail
+    pub snake: Vec<Coords>,
+    pub food: Vec<Coords>,
+    /// Bodies of the rival snakes, from the head to the tail
+    pub rivals: Vec<Vec<Coords>>,
+    pub obstacles: &'a [Coords],
+    pub portals: &'a [(Coords, Coords)],
+    pub score: u32,
+    pub lives: u32,
+    /// The snake is waiting to respawn, the moves are ignored
+    pub respawning: bool,
+}
+
+impl<'a> BotState<'a> {
+    pub fn new(game: &'a GameState) -> Self {
+        Self {
+            tick: game.tick(),
+            width: game.width(),
+            height: game.height(),
+            solid_walls: game.conf().solid_walls,
+            dir: game.snake_dir().into(),
+            snake: game.snake_iter().collect(),
+            food: game.food().iter().map(|f| f.pos).collect(),
+            rivals: game
+                .rivals()
+                .iter()
+                .map(|r| r.body.iter().copied().collect())
+                .collect(),
+            obstacles: &game.conf().obstacles,
+            portals: game.portals(),
+            score: game.score(),
+            lives: game.lives_left(),
+            respawning: matches!(game.status(), GameStatus::Respawning { .. }),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Reply {
+    #[serde(rename = "move")]
+    mv: Move,
+}
+
+/// Why the bot lost the game without playing it to the end
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum Forfeit {
+    #[error("no move in {0:?}")]
+    Timeout(Duration),
+    #[error("malformed reply {0:?}")]
+    Malformed(String),
+    #[error("more than one reply, {0:?} is extra")]
+    ExtraReply(String),
+    #[error("the bot exited")]
+    Exited,
+}
+
+pub struct Bot {
+    child: Child,
+    stdin: BufWriter<ChildStdin>,
+    lines: Receiver<String>,
+    timeout: Duration,
+    forfeit: Option<Forfeit>,
+}
+
+impl Bot {
+    /// Starts the bot. `cmd` is the program followed by its arguments, see [`split_command`].
+    pub fn spawn(cmd: &str, timeout: Duration) -> eyre::Result<Self> {
+        let words = split_command(cmd)?;
+        let Some((program, args)) = words.split_first() else {
+            eyre::bail!("Bot command is empty");
+        };
+
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            // the bot's logs would be drawn over the game
+            .stderr(Stdio::null())
+            .spawn()
+            .wrap_err_with(|| format!("Unable to start bot {cmd:?}"))?;
+
+        let stdin = BufWriter::new(child.stdin.take().expect("stdin is piped"));
+        let stdout = child.stdout.take().expect("stdout is piped");
+
+        // reading is done on another thread, so waiting for the move can time out.
+        // A bot that writes too much blocks instead of filling up the memory.
+        let (tx, lines) = mpsc::sync_channel(1);
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            child,
+            stdin,
+            lines,
+            timeout,
+            forfeit: None,
+        })
+    }
+
+    /// Reason of the forfeit, if the bot forfeited
+    pub fn forfeit(&self) -> Option<&Forfeit> {
+        self.forfeit.as_ref()
+    }
+
+    /// Sends the state of the game to the bot and waits for its move.
+    /// Once the bot forfeits, every call returns the same error.
+    pub fn next_turn(&mut self, game: &GameState) -> Result<Option<Dir>, Forfeit> {
+        if let Some(forfeit) = &self.forfeit {
+            return Err(forfeit.clone());
+        }
+
+        let result = self.ask(game);
+        if let Err(forfeit) = &result {
+            self.forfeit = Some(forfeit.clone());
+        }
+        result
+    }
+
+    fn ask(&mut self, game: &GameState) -> Result<Option<Dir>, Forfeit> {
+        // a line left from the previous turn would be taken as the reply to this one
+        match self.lines.try_recv() {
+            Ok(line) => return Err(Forfeit::ExtraReply(line)),
+            Err(TryRecvError::Empty) => (),
+            Err(TryRecvError::Disconnected) => return Err(Forfeit::Exited),
+        }
+
+        let sent = serde_json::to_writer(&mut self.stdin, &BotState::new(game))
+            .map_err(std::io::Error::from)
+            .and_then(|()| writeln!(self.stdin))
+            .and_then(|()| self.stdin.flush());
+        if sent.is_err() {
+            return Err(Forfeit::Exited);
+        }
+
+        let line = match self.lines.recv_timeout(self.timeout) {
+            Ok(line) => line,
+            Err(RecvTimeoutError::Timeout) => return Err(Forfeit::Timeout(self.timeout)),
+            Err(RecvTimeoutError::Disconnected) => return Err(Forfeit::Exited),
+        };
+        match serde_json::from_str::<Reply>(&line) {
+            Ok(reply) => Ok(reply.mv.turn(game.snake_dir())),
+            Err(_) => Err(Forfeit::Malformed(line)),
+        }
+    }
+}
+
+impl Drop for Bot {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Splits the command into words on spaces, like a shell does. Single and double quotes
+/// keep the spaces in a word, and a backslash escapes the next character outside of
+/// single quotes.
+pub fn split_command(cmd: &str) -> eyre::Result<Vec<String>> {
+    let mut words = Vec::new();
+    // `None` between the words, so quoted empty words are kept
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = cmd.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (Some('\''), c) => word.get_or_insert_with(String::new).push(c),
+            (_, '\\') => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => eyre::bail!("Bot command {cmd:?} ends with a backslash"),
+            },
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (_, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quote.is_some() {
+        eyre::bail!("Bot command {cmd:?} has an unclosed quote");
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Plays the game with the bot without the terminal, until the game ends, the bot
+/// forfeits or `max_ticks` pass. Returns the final state and the replay of the game.
+pub fn play(mut game: GameState, bot: &mut Bot, max_ticks: u64) -> (GameState, Replay) {
+    let mut replay = Replay::new(game.conf().clone());
+
+    while game.tick() < max_ticks {
+        let Ok(turn) = bot.next_turn(&game) else {
+            break;
+        };
+        // same as with the player, turns are not made during the respawn countdown
+        let turn = turn.filter(|_| game.status() == GameStatus::Ongoing);
+        if let Some(dir) = turn {
+            replay.record_turn(game.tick(), dir);
+        }
+
+        game.make_step(turn);
+        if let GameStatus::Dead | GameStatus::Win = game.status() {
+            break;
+        }
+    }
+
+    replay.finish(game.tick());
+    (game, replay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_turn_the_snake() {
+        assert_eq!(Move::Up.turn(Dir::Right), Some(Dir::Up));
+        assert_eq!(Move::Left.turn(Dir::Right), None);
+        assert_eq!(Move::Right.turn(Dir::Right), None);
+        assert_eq!(Move::Straight.turn(Dir::Down), None);
+
+        let reply: Reply = serde_json::from_str(r#"{"move": "down"}"#).unwrap();
+        assert_eq!(reply.mv, Move::Down);
+        assert!(serde_json::from_str::<Reply>(r#"{"move": "north"}"#).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn bots_forfeit() {
+        let new_game = || GameState::new(crate::game::test_conf());
+        let timeout = Duration::from_secs(5);
+
+        // cat replies with the state, which is not a move
+        let mut bot = Bot::spawn("cat", timeout).unwrap();
+        let (game, _) = play(new_game(), &mut bot, 100);
+        assert!(matches!(bot.forfeit(), Some(Forfeit::Malformed(_))));
+        assert_eq!(game.tick(), 0);
+
+        let mut bot = Bot::spawn("true", timeout).unwrap();
+        play(new_game(), &mut bot, 100);
+        assert_eq!(bot.forfeit(), Some(&Forfeit::Exited));
+
+        let mut bot = Bot::spawn("sleep 10", Duration::from_millis(50)).unwrap();
+        play(new_game(), &mut bot, 100);
+        assert!(matches!(bot.forfeit(), Some(Forfeit::Timeout(_))));
+
+        // the second reply is taken as extra, not as the reply to the next state
+        let twice = r#"sh -c 'while read -r l; do echo "$1"; echo "$1"; done' - '{"move": "up"}'"#;
+        let mut bot = Bot::spawn(twice, timeout).unwrap();
+        let game = new_game();
+        assert_eq!(bot.next_turn(&game), Ok(Some(Dir::Up)));
+        thread::sleep(Duration::from_millis(100));
+        assert!(matches!(bot.next_turn(&game), Err(Forfeit::ExtraReply(_))));
+    }
+
+    #[test]
+    fn commands_are_split() {
+        let split = |cmd| split_command(cmd).unwrap();
+        assert_eq!(split("  python3   bot.py "), ["python3", "bot.py"]);
+        assert_eq!(
+            split(r#"sh -c 'echo "a  b"' "it's \"so\"" a\ b ''"#),
+            ["sh", "-c", r#"echo "a  b""#, r#"it's "so""#, "a b", ""]
+        );
+        assert!(split("").is_empty());
+        assert!(split_command("sh -c 'echo").is_err());
+        assert!(split_command(r"sh \").is_err());
+    }
+}
@@ -0,0 +1,420 @@
+//! Campaign: a sequence of levels, each with its own field and a goal.
+//! Finishing a level unlocks the next one, progress is saved locally.
+
+use std::path::{Path, PathBuf};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    terminal,
+};
+use rand::random;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ai::Strategy,
+    game::{
+        Coords, FoodMovement, FoodPlacement, GameConf, GameState, GameStatus, Goal, SelfHit,
+        SpeedCurve,
+    },
+    game_loop::{self, RunOptions, SpeedKeys},
+    stats::ThemeNames,
+    storage,
+    themes::FullTheme,
+};
+
+pub struct Level {
+    pub name: &'static str,
+    /// Rows of the field, `#` is an obstacle and `.` is an empty cell
+    pub map: &'static [&'static str],
+    pub speed: u32,
+    pub walls: bool,
+    pub food: u32,
+    pub portals: u32,
+    pub goal: Goal,
+}
+
+impl Level {
+    pub fn obstacles(&self) -> Vec<Coords> {
+        let mut obstacles = Vec::new();
+        for (y, row) in self.map.iter().enumerate() {
+            for (x, ch) in row.chars().enumerate() {
+                if ch == '#' {
+                    obstacles.push(Coords { x, y });
+                }
+            }
+        }
+        obstacles
+    }
+
+    pub fn conf(&self, seed: u64) -> GameConf {
+        GameConf {
+            // the speed is set by the level
+            food_to_speed_up: 0,
+            food_n: self.food,
+            initial_speed: f64::from(self.speed),
+            height: self.map.len(),
+            width: self.map[0].len(),
+            initial_length: 3,
+            seed,
+            solid_walls: self.walls,
+            portals: self.portals,
+            on_self_hit: SelfHit::Die,
+            lives: 1,
+            obstacles: self.obstacles(),
+            goal: Some(self.goal),
+            // food enclosed by obstacles could make the goal impossible
+            food_placement: FoodPlacement::Fair,
+            moving_food: 0,
+            food_movement: FoodMovement::Random,
+            food_move_every: 1,
+            rivals: 0,
+            rival_strategy: Strategy::Greedy,
+            speed_curve: SpeedCurve::Linear,
+            speed_steps: Vec::new(),
+            max_speed: None,
+        }
+    }
+}
+
+pub const LEVELS: &[Level] = &[
+    Level {
+        name: "Warm-up",
+        map: &[
+            "................",
+            "................",
+            "................",
+            "................",
+            "................",
+            "................",
+            "................",
+            "................",
+            "................",
+            "................",
+        ],
+        speed: 5,
+        walls: false,
+        food: 1,
+        portals: 0,
+        goal: Goal::EatFood(5),
+    },
+    Level {
+        name: "Walled garden",
+        map: &[
+            "..................",
+            "..................",
+            "..................",
+            "..................",
+            "..................",
+            "..................",
+            "..................",
+            "..................",
+            "..................",
+            "..................",
+        ],
+        speed: 6,
+        walls: true,
+        food: 1,
+        portals: 0,
+        goal: Goal::EatFood(8),
+    },
+    Level {
+        name: "Pillars",
+        map: &[
+            "....................",
+            "....................",
+            "...##..........##...",
+            "...##..........##...",
+            "....................",
+            "....................",
+            "....................",
+            "....................",
+            "....................",
+            "...##..........##...",
+            "...##..........##...",
+            "....................",
+        ],
+        speed: 6,
+        walls: false,
+        food: 2,
+        portals: 0,
+        goal: Goal::ReachLength(15),
+    },
+    Level {
+        name: "Corridors",
+        map: &[
+            "......................",
+            "......................",
+            "...################...",
+            "......................",
+            "......................",
+            "......................",
+            "......................",
+            "......................",
+            "......................",
+            "...################...",
+            "......................",
+            "......................",
+        ],
+        speed: 7,
+        walls: true,
+        food: 2,
+        portals: 0,
+        goal: Goal::Survive(60),
+    },
+    Level {
+        name: "Wormholes",
+        map: &[
+            "......................",
+            "......................",
+            "..####..........####..",
+            "..#................#..",
+            "..#................#..",
+            "......................",
+            "......................",
+            "......................",
+            "......................",
+            "......................",
+            "..#................#..",
+            "..#................#..",
+            "..####..........####..",
+            "......................",
+        ],
+        speed: 8,
+        walls: false,
+        food: 1,
+        portals: 2,
+        goal: Goal::EatFood(15),
+    },
+    Level {
+        name: "Gauntlet",
+        map: &[
+            "........................",
+            "........................",
+            "..##....##....##....##..",
+            "..##....##....##....##..",
+            "........................",
+            "....##....##....##......",
+            "........................",
+            "........................",
+            "........................",
+            "......##....##....##....",
+            "........................",
+            "..##....##....##....##..",
+            "..##....##....##....##..",
+            "........................",
+        ],
+        speed: 9,
+        walls: true,
+        food: 1,
+        portals: 0,
+        goal: Goal::ReachLength(30),
+    },
+];
+
+/// Campaign progress saved between the runs of the game
+#[derive(Default, Serialize, Deserialize)]
+pub struct Progress {
+    /// Number of levels finished, which is also the index of the first unfinished one
+    pub completed: usize,
+}
+
+impl Progress {
+    /// Default location of the progress file
+    pub fn default_path() -> Option<PathBuf> {
+        Some(dirs::data_dir()?.join("snekgame").join("campaign.json"))
+    }
+
+    /// Loads the progress, if the file doesn't exist yet nothing is finished
+    pub fn load(path: &Path) -> eyre::Result<Self> {
+        storage::load_json(path)
+    }
+
+    pub fn save(&self, path: &Path) -> eyre::Result<()> {
+        storage::save_json(path, self)
+    }
+}
+
+/// Plays the levels one after another, starting from level number `start`
+/// or from the first unfinished one. The games are added to the lifetime stats with `theme_names`.
+pub fn run(
+    start: Option<usize>,
+    theme: &FullTheme,
+    theme_names: &ThemeNames,
+    frame_rate: u32,
+) -> eyre::Result<()> {
+    let path = Progress::default_path();
+    // without the saved progress the campaign can still be played from the start,
+    // the player is warned after the game, where the warning isn't cleared away
+    let (mut progress, mut load_error) = match path.as_deref().map(Progress::load) {
+        Some(Ok(progress)) => (progress, None),
+        Some(Err(e)) => (Progress::default(), Some(e)),
+        None => (Progress::default(), None),
+    };
+
+    let mut level = match start {
+        Some(n) if n == 0 || n > LEVELS.len() => {
+            eyre::bail!("There are only levels 1 to {}", LEVELS.len())
+        }
+        Some(n) if n - 1 > progress.completed => {
+            eyre::bail!(
+                "Level {n} is locked, finish level {} first",
+                progress.completed + 1
+            )
+        }
+        Some(n) => n - 1,
+        // start again from the last level if everything is finished
+        None => progress.completed.min(LEVELS.len() - 1),
+    };
+
+    loop {
+        let game = GameState::new(LEVELS[level].conf(random()));
+        let options = RunOptions {
+            playback: None,
+            bot: None,
+            practice: None,
+            asciicast: None,
+            frame_rate,
+            measure_latency: false,
+            fit_field: false,
+            speed_keys: SpeedKeys::default(),
+            stats: Some(theme_names.clone()),
+        };
+        let (game, _) = game_loop::run(game, theme, options)?;
+        if let Some(e) = load_error.take() {
+            eprintln!("Unable to load campaign progress: {e:#}");
+        }
+
+        let won = match game.status() {
+            GameStatus::Win => true,
+            GameStatus::Dead => false,
+            // the player quit
+            GameStatus::Ongoing | GameStatus::Respawning { .. } => return Ok(()),
+        };
+
+        if won && level == progress.completed {
+            progress.completed += 1;
+            if let Err(e) = path.as_deref().map_or(Ok(()), |path| progress.save(path)) {
+                eprintln!("Unable to save campaign progress: {e:#}");
+            }
+        }
+
+        print_progress(&progress, level, won);
+
+        if won {
+            if level + 1 == LEVELS.len() {
+                println!("Campaign complete!");
+                return Ok(());
+            }
+            level += 1;
+        }
+
+        let action = if won {
+            "play the next level"
+        } else {
+            "try again"
+        };
+        println!("Enter: {action}   q: quit");
+        if !wait_for_continue()? {
+            return Ok(());
+        }
+    }
+}
+
+/// Progression screen shown between the levels
+fn print_progress(progress: &Progress, played: usize, won: bool) {
+    let result = if won { "complete" } else { "failed" };
+    println!("Level {} \"{}\" {result}", played + 1, LEVELS[played].name);
+    println!();
+
+    for (i, level) in LEVELS.iter().enumerate() {
+        let mark = match () {
+            _ if i < progress.completed => "✓",
+            _ if i == progress.completed => ">",
+            _ => " ",
+        };
+        println!("  {mark} {:>2}. {:<16} {}", i + 1, level.name, level.goal);
+    }
+    println!();
+}
+
+/// Waits for the player to press Enter to continue or q to quit
+fn wait_for_continue() -> eyre::Result<bool> {
+    terminal::enable_raw_mode()?;
+    let result = loop {
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => match key.code {
+                KeyCode::Enter | KeyCode::Char(' ') => break Ok(true),
+                KeyCode::Char('q') | KeyCode::Esc => break Ok(false),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    break Ok(false)
+                }
+                _ => (),
+            },
+            Ok(_) => (),
+            Err(e) => break Err(e),
+        }
+    };
+    terminal::disable_raw_mode()?;
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Dir;
+
+    #[test]
+    fn levels_are_playable() {
+        for level in LEVELS {
+            let width = level.map[0].len();
+            assert!(
+                level.map.iter().all(|row| row.len() == width),
+                "{}: rows of the map have different lengths",
+                level.name
+            );
+
+            level.conf(0).validate().unwrap();
+
+            // portals and food are placed randomly, so the start is checked with many seeds
+            let obstacles = level.obstacles();
+            for seed in 0..20 {
+                let game = GameState::new(level.conf(seed));
+                let portals: Vec<Coords> =
+                    game.portals().iter().flat_map(|&(a, b)| [a, b]).collect();
+                let blocked = |c: &Coords| obstacles.contains(c) || portals.contains(c);
+
+                // the snake must not start on an obstacle or portal, or right in front of an obstacle
+                let mut ahead = game.snake_iter().next().unwrap();
+                for _ in 0..5 {
+                    // straight ahead on the map, the portals are checked below
+                    ahead = ahead.move_wrapping(Dir::Right, game.width(), game.height());
+                    assert!(
+                        !obstacles.contains(&ahead),
+                        "{} (seed {seed}): obstacle ahead",
+                        level.name
+                    );
+                }
+                for c in game.snake_iter() {
+                    assert!(
+                        !blocked(&c),
+                        "{} (seed {seed}): obstacle or portal under snake",
+                        level.name
+                    );
+                }
+                for food in game.food() {
+                    assert!(
+                        !blocked(&food.pos),
+                        "{} (seed {seed}): food on an obstacle or portal",
+                        level.name
+                    );
+                }
+                assert_eq!(
+                    game.status(),
+                    GameStatus::Ongoing,
+                    "{} (seed {seed})",
+                    level.name
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! Game codes: short strings that contain everything needed to start the same game
+
+use data_encoding::BASE32_NOPAD;
+use eyre::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::GameConf,
+    themes::{BoardBuiltin, FoodBuiltin, SnakeBuiltin},
+};
+
+/// Version of the code format. Fields are encoded by their order and enum variants by
+/// their index, so it must be bumped on any change to the encoded types.
+const VERSION: u8 = 5;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GameCode {
+    pub conf: GameConf,
+    pub snake_theme: SnakeBuiltin,
+    pub board_theme: BoardBuiltin,
+    pub food_theme: FoodBuiltin,
+}
+
+impl GameCode {
+    /// The version followed by the compact binary encoding, all in base32
+    pub fn encode(&self) -> String {
+        let mut bytes = vec![VERSION];
+        bytes.extend(postcard::to_stdvec(self).expect("game code can always be encoded"));
+        BASE32_NOPAD.encode(&bytes)
+    }
+
+    pub fn decode(code: &str) -> eyre::Result<Self> {
+        let bytes = BASE32_NOPAD
+            .decode(code.trim().to_ascii_uppercase().as_bytes())
+            .wrap_err("Invalid game code")?;
+
+        match bytes.first() {
+            None => eyre::bail!("Game code is empty"),
+            Some(&VERSION) => (),
+            Some(version) => eyre::bail!(
+                "Game code has version {version}, but only version {VERSION} is supported"
+            ),
+        }
+
+        let (code, rest): (Self, _) =
+            postcard::take_from_bytes(&bytes[1..]).wrap_err("Invalid game code")?;
+        if !rest.is_empty() {
+            eyre::bail!("Invalid game code");
+        }
+        Ok(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::Strategy;
+    use crate::game::{Coords, FoodMovement, FoodPlacement, Goal, SelfHit, SpeedCurve};
+
+    fn code() -> GameCode {
+        GameCode {
+            conf: GameConf {
+                food_to_speed_up: 4,
+                food_n: 2,
+                initial_speed: 6.0,
+                height: 15,
+                width: 25,
+                initial_length: 3,
+                seed: 1234567890,
+                solid_walls: true,
+                portals: 1,
+                on_self_hit: SelfHit::Cut,
+                lives: 3,
+                obstacles: vec![Coords { x: 1, y: 2 }],
+                goal: Some(Goal::Survive(60)),
+                food_placement: FoodPlacement::Clustered,
+                moving_food: 1,
+                food_movement: FoodMovement::Flee,
+                food_move_every: 3,
+                rivals: 2,
+                rival_strategy: Strategy::Safe,
+                speed_curve: SpeedCurve::Linear,
+                speed_steps: Vec::new(),
+                max_speed: None,
+            },
+            snake_theme: SnakeBuiltin::Line,
+            board_theme: BoardBuiltin::Double,
+            food_theme: FoodBuiltin::Ascii,
+        }
+    }
+
+    #[test]
+    fn decodes_what_is_encoded() {
+        let encoded = code().encode();
+        let decoded = GameCode::decode(&encoded.to_lowercase()).unwrap();
+        assert_eq!(decoded.encode(), encoded);
+        assert_eq!(decoded.conf.seed, 1234567890);
+        assert_eq!(decoded.conf.goal, Some(Goal::Survive(60)));
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let mut bytes = BASE32_NOPAD.decode(code().encode().as_bytes()).unwrap();
+        bytes[0] = VERSION + 1;
+        let err = GameCode::decode(&BASE32_NOPAD.encode(&bytes)).unwrap_err();
+        assert!(err.to_string().contains("version"), "{err}");
+
+        assert!(GameCode::decode("not a code").is_err());
+    }
+}
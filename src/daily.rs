@@ -0,0 +1,141 @@
+//! Daily challenge: everyone playing on the same UTC date gets the same game
+
+use std::time::SystemTime;
+
+use crate::{
+    ai::Strategy,
+    game::{FoodMovement, FoodPlacement, GameConf, GameState, Goal, SelfHit, SpeedCurve},
+    highscores::{Entry, HighScores},
+    replay::Replay,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Date {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    /// Current date in UTC
+    pub fn today() -> Self {
+        let secs = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Self::from_days(secs as i64 / 86400)
+    }
+
+    /// Date that is `days` after 1970-01-01.
+    /// See: <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+    pub fn from_days(days: i64) -> Self {
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + i64::from(month <= 2);
+        Self { year, month, day }
+    }
+
+    pub fn seed(&self) -> u64 {
+        fnv1a(format!("snekgame daily {self}").as_bytes())
+    }
+}
+
+impl std::fmt::Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// 64 bit FNV-1a hash, which is stable across versions and platforms unlike `std::hash`
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &b in bytes {
+        hash ^= u64::from(b);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Config of the challenge, it's the same every day except the seed
+pub fn conf(date: Date) -> GameConf {
+    GameConf {
+        food_to_speed_up: 4,
+        food_n: 1,
+        initial_speed: 6.0,
+        height: 15,
+        width: 25,
+        initial_length: 3,
+        seed: date.seed(),
+        solid_walls: true,
+        portals: 0,
+        on_self_hit: SelfHit::Die,
+        lives: 1,
+        obstacles: Vec::new(),
+        goal: Some(Goal::TimeAttack(120)),
+        food_placement: FoodPlacement::Fair,
+        moving_food: 0,
+        food_movement: FoodMovement::Random,
+        food_move_every: 1,
+        rivals: 0,
+        rival_strategy: Strategy::Greedy,
+        speed_curve: SpeedCurve::Linear,
+        speed_steps: Vec::new(),
+        max_speed: None,
+    }
+}
+
+/// Records the attempt if it's the first one today and prints the result to share
+pub fn record_attempt(date: Date, game: &GameState, replay: &Replay) -> eyre::Result<()> {
+    match save_attempt(date, game) {
+        Ok(None) => (),
+        Ok(Some(first)) => println!(
+            "You already played the challenge of {date} with score {}, only the first attempt is scored",
+            first.score
+        ),
+        Err(e) => eprintln!("Unable to save the daily challenge result: {e:#}"),
+    }
+
+    let replay_hash = fnv1a(&serde_json::to_vec(replay)?);
+    println!(
+        "snekgame daily {date}: score {}, length {}, replay {replay_hash:016x}",
+        game.score(),
+        game.snake_iter().count(),
+    );
+    Ok(())
+}
+
+/// Saves the attempt into the high scores.
+/// Returns the first attempt of the day, if this one is not the first.
+fn save_attempt(date: Date, game: &GameState) -> eyre::Result<Option<Entry>> {
+    let Some(path) = HighScores::default_path() else {
+        return Ok(None);
+    };
+    let mut scores = HighScores::load(&path)?;
+    if let Some(first) = scores.daily(&date.to_string()) {
+        return Ok(Some(first.clone()));
+    }
+    scores.add_daily(date.to_string(), Entry::from_game(game));
+    scores.save(&path)?;
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_from_days() {
+        let date = |year, month, day| Date { year, month, day };
+        assert_eq!(Date::from_days(0), date(1970, 1, 1));
+        assert_eq!(Date::from_days(59), date(1970, 3, 1));
+        assert_eq!(Date::from_days(11016), date(2000, 2, 29));
+        assert_eq!(Date::from_days(20744), date(2026, 10, 18));
+        assert_eq!(Date::from_days(-1), date(1969, 12, 31));
+    }
+}
@@ -0,0 +1,404 @@
+//! Reinforcement learning environment over [`GameState`], in the style of gym:
+//! `reset` starts a new game and `step` makes one step of it with the agent's action.
+//! Nothing here touches the terminal.
+
+use ndarray::{Array1, Array3};
+
+use crate::game::{Coords, Dir, GameConf, GameState, GameStatus};
+
+/// What the agent does on a step
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Keep going in the same direction
+    Straight,
+    /// Turn to the direction. Turning back is the same as going straight.
+    Turn(Dir),
+}
+
+impl Action {
+    /// Number of discrete actions, see [`Action::from_index`]
+    pub const COUNT: usize = 5;
+
+    /// Action for the index of the discrete action space:
+    /// 0 is straight, then up, down, left and right
+    pub fn from_index(i: usize) -> Option<Self> {
+        match i {
+            0 => Some(Action::Straight),
+            1 => Some(Action::Turn(Dir::Up)),
+            2 => Some(Action::Turn(Dir::Down)),
+            3 => Some(Action::Turn(Dir::Left)),
+            4 => Some(Action::Turn(Dir::Right)),
+            _ => None,
+        }
+    }
+}
+
+/// How the game is shown to the agent
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObservationKind {
+    /// [`Observation::Grid`]
+    Grid,
+    /// [`Observation::Features`]
+    Features,
+}
+
+/// Channels of [`Observation::Grid`]
+pub mod channel {
+    pub const HEAD: usize = 0;
+    pub const BODY: usize = 1;
+    pub const TAIL: usize = 2;
+    pub const FOOD: usize = 3;
+    /// Obstacles, portals and rival snakes
+    pub const OTHER: usize = 4;
+    pub const COUNT: usize = 5;
+}
+
+/// Number of values in [`Observation::Features`]
+pub const FEATURE_COUNT: usize = 14;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Observation {
+    /// One-hot `[channel, y, x]` grid of the field, see [`channel`]
+    Grid(Array3<f32>),
+    /// Hand made features:
+    /// - danger straight ahead, to the left and to the right (3)
+    /// - direction of the snake, one-hot up, down, left, right (4)
+    /// - closest food is up, down, left, right of the head (4)
+    /// - distance to the closest food along x and y, relative to the field size (2)
+    /// - length of the snake, relative to the field size (1)
+    Features(Array1<f32>),
+}
+
+/// Rewards for the events of a step, which are summed up
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rewards {
+    pub food: f32,
+    /// Losing a life, or the game
+    pub death: f32,
+    pub win: f32,
+    /// Given on every step, negative values make the agent hurry
+    pub step: f32,
+    /// Given for getting one cell closer to the closest food, and taken for getting further
+    pub closer: f32,
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Self {
+            food: 1.0,
+            death: -1.0,
+            win: 1.0,
+            step: 0.0,
+            closer: 0.0,
+        }
+    }
+}
+
+/// Result of a step
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub observation: Observation,
+    pub reward: f32,
+    /// The game is over, and the environment must be reset
+    pub done: bool,
+}
+
+pub struct SnakeEnv {
+    conf: GameConf,
+    game: GameState,
+    rewards: Rewards,
+    observation: ObservationKind,
+}
+
+impl SnakeEnv {
+    /// Creates the environment for games with `conf`, the seed of `conf` is used until reset.
+    /// Fails if the game can't be played with `conf`, see [`GameConf::validate`].
+    pub fn new(
+        conf: GameConf,
+        rewards: Rewards,
+        observation: ObservationKind,
+    ) -> eyre::Result<Self> {
+        conf.validate()?;
+        Ok(Self {
+            game: GameState::new(conf.clone()),
+            conf,
+            rewards,
+            observation,
+        })
+    }
+
+    /// Starts a new game with `seed`
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.conf.seed = seed;
+        self.game = GameState::new(self.conf.clone());
+        self.observe()
+    }
+
+    pub fn step(&mut self, action: Action) -> Step {
+        let turn = match action {
+            Action::Turn(dir) if dir.is_perpendicular(self.game.snake_dir()) => Some(dir),
+            Action::Turn(_) | Action::Straight => None,
+        };
+
+        let score = self.game.score();
+        let lives = self.game.lives_left();
+        let distance = food_distance(&self.game);
+
+        self.game.make_step(turn);
+
+        let mut reward = self.rewards.step;
+        if self.game.score() > score {
+            reward += self.rewards.food * (self.game.score() - score) as f32;
+        }
+        if self.game.lives_left() < lives || self.game.status() == GameStatus::Dead {
+            reward += self.rewards.death;
+        }
+        if self.game.status() == GameStatus::Win {
+            reward += self.rewards.win;
+        }
+        if let (Some(before), Some(after)) = (distance, food_distance(&self.game)) {
+            reward += self.rewards.closer * (before as f32 - after as f32).clamp(-1.0, 1.0);
+        }
+
+        Step {
+            observation: self.observe(),
+            reward,
+            done: matches!(self.game.status(), GameStatus::Dead | GameStatus::Win),
+        }
+    }
+
+    pub fn game(&self) -> &GameState {
+        &self.game
+    }
+
+    pub fn observe(&self) -> Observation {
+        match self.observation {
+            ObservationKind::Grid => Observation::Grid(grid(&self.game)),
+            ObservationKind::Features => Observation::Features(features(&self.game)),
+        }
+    }
+}
+
+/// Many environments stepped together. Finished games are reset right away,
+/// with seeds continuing from the ones given to [`BatchEnv::reset`].
+pub struct BatchEnv {
+    envs: Vec<SnakeEnv>,
+    next_seed: u64,
+}
+
+impl BatchEnv {
+    /// Fails if the game can't be played with `conf`, see [`GameConf::validate`]
+    pub fn new(
+        n: usize,
+        conf: GameConf,
+        rewards: Rewards,
+        observation: ObservationKind,
+    ) -> eyre::Result<Self> {
+        Ok(Self {
+            envs: (0..n)
+                .map(|_| SnakeEnv::new(conf.clone(), rewards, observation))
+                .collect::<eyre::Result<_>>()?,
+            next_seed: conf.seed,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.envs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.envs.is_empty()
+    }
+
+    /// Resets the games with seeds `seed`, `seed + 1`, ...
+    pub fn reset(&mut self, seed: u64) -> Vec<Observation> {
+        self.next_seed = seed;
+        self.envs
+            .iter_mut()
+            .map(|env| {
+                let seed = self.next_seed;
+                self.next_seed = self.next_seed.wrapping_add(1);
+                env.reset(seed)
+            })
+            .collect()
+    }
+
+    /// Steps every game with its action. For finished games the observation
+    /// is of the new game, which replaced it.
+    pub fn step(&mut self, actions: &[Action]) -> Vec<Step> {
+        assert_eq!(actions.len(), self.envs.len(), "one action for every game");
+
+        self.envs
+            .iter_mut()
+            .zip(actions)
+            .map(|(env, &action)| {
+                let mut step = env.step(action);
+                if step.done {
+                    step.observation = env.reset(self.next_seed);
+                    self.next_seed = self.next_seed.wrapping_add(1);
+                }
+                step
+            })
+            .collect()
+    }
+
+    pub fn envs(&self) -> &[SnakeEnv] {
+        &self.envs
+    }
+}
+
+fn grid(game: &GameState) -> Array3<f32> {
+    let mut grid = Array3::zeros([channel::COUNT, game.height(), game.width()]);
+    let mut set = |ch: usize, c: Coords| grid[[ch, c.y, c.x]] = 1.0;
+
+    let len = game.snake_iter().count();
+    for (i, c) in game.snake_iter().enumerate() {
+        match i {
+            0 => set(channel::HEAD, c),
+            i if i == len - 1 => set(channel::TAIL, c),
+            _ => set(channel::BODY, c),
+        }
+    }
+    for food in game.food() {
+        set(channel::FOOD, food.pos);
+    }
+    for &c in &game.conf().obstacles {
+        set(channel::OTHER, c);
+    }
+    for &(a, b) in game.portals() {
+        set(channel::OTHER, a);
+        set(channel::OTHER, b);
+    }
+    for rival in game.rivals() {
+        for &c in &rival.body {
+            set(channel::OTHER, c);
+        }
+    }
+    grid
+}
+
+fn features(game: &GameState) -> Array1<f32> {
+    let head = game.snake_iter().next().unwrap();
+    let dir = game.snake_dir();
+    let (left, right) = match dir {
+        Dir::Up => (Dir::Left, Dir::Right),
+        Dir::Down => (Dir::Right, Dir::Left),
+        Dir::Left => (Dir::Down, Dir::Up),
+        Dir::Right => (Dir::Up, Dir::Down),
+    };
+    let danger = |dir| match game.neighbor(head, dir) {
+        None => 1.0,
+        Some(c) if game.is_blocked(c) => 1.0,
+        Some(_) => 0.0,
+    };
+    let flag = |b: bool| if b { 1.0 } else { 0.0 };
+
+    let food = closest_food(game);
+    let (w, h) = (game.width() as f32, game.height() as f32);
+    let (dx, dy) = match food {
+        Some(f) => (f.x as f32 - head.x as f32, f.y as f32 - head.y as f32),
+        None => (0.0, 0.0),
+    };
+
+    Array1::from(vec![
+        danger(dir),
+        danger(left),
+        danger(right),
+        flag(dir == Dir::Up),
+        flag(dir == Dir::Down),
+        flag(dir == Dir::Left),
+        flag(dir == Dir::Right),
+        flag(dy < 0.0),
+        flag(dy > 0.0),
+        flag(dx < 0.0),
+        flag(dx > 0.0),
+        dx.abs() / w,
+        dy.abs() / h,
+        game.snake_iter().count() as f32 / (w * h),
+    ])
+}
+
+fn closest_food(game: &GameState) -> Option<Coords> {
+    let head = game.snake_iter().next().unwrap();
+    game.food()
+        .iter()
+        .map(|f| f.pos)
+        .min_by_key(|f| f.x.abs_diff(head.x) + f.y.abs_diff(head.y))
+}
+
+fn food_distance(game: &GameState) -> Option<usize> {
+    let head = game.snake_iter().next().unwrap();
+    closest_food(game).map(|f| f.x.abs_diff(head.x) + f.y.abs_diff(head.y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conf() -> GameConf {
+        GameConf {
+            width: 8,
+            height: 6,
+            ..crate::game::test_conf()
+        }
+    }
+
+    #[test]
+    fn steps_until_the_wall() {
+        let mut env = SnakeEnv::new(conf(), Rewards::default(), ObservationKind::Grid).unwrap();
+        let Observation::Grid(grid) = env.reset(3) else {
+            panic!("grid observation expected");
+        };
+        assert_eq!(grid.shape(), [channel::COUNT, 6, 8]);
+        assert_eq!(grid.index_axis(ndarray::Axis(0), channel::HEAD).sum(), 1.0);
+        assert_eq!(grid.index_axis(ndarray::Axis(0), channel::BODY).sum(), 1.0);
+
+        // the snake goes right into the wall
+        let mut steps = Vec::new();
+        loop {
+            let step = env.step(Action::Straight);
+            let done = step.done;
+            steps.push(step);
+            if done {
+                break;
+            }
+        }
+        assert_eq!(steps.last().unwrap().reward, -1.0);
+
+        // the same seed plays the same game
+        env.reset(3);
+        for step in &steps {
+            assert_eq!(&env.step(Action::Straight), step);
+        }
+    }
+
+    #[test]
+    fn unplayable_configs_are_rejected() {
+        let empty = GameConf { width: 0, ..conf() };
+        let rewards = Rewards::default();
+        assert!(SnakeEnv::new(empty.clone(), rewards, ObservationKind::Grid).is_err());
+        assert!(BatchEnv::new(2, empty, rewards, ObservationKind::Grid).is_err());
+    }
+
+    #[test]
+    fn batch_resets_finished_games() {
+        let mut batch =
+            BatchEnv::new(3, conf(), Rewards::default(), ObservationKind::Features).unwrap();
+        let observations = batch.reset(10);
+        assert_eq!(observations.len(), 3);
+        for o in &observations {
+            let Observation::Features(f) = o else {
+                panic!("features expected");
+            };
+            assert_eq!(f.len(), FEATURE_COUNT);
+        }
+
+        let mut done = 0;
+        for _ in 0..10 {
+            let steps = batch.step(&[Action::Straight; 3]);
+            done += steps.iter().filter(|s| s.done).count();
+        }
+        assert!(done >= 3);
+        assert!(batch.envs().iter().all(|e| e.game().tick() < 10));
+    }
+}
@@ -0,0 +1,275 @@
+//! Export of the game as SVG or HTML, for docs and bug reports
+
+use std::{fmt::Write as _, io::Write, path::Path, time::Duration};
+
+use crossterm::style::Color;
+
+use crate::{
+    game::{GameState, GameStatus},
+    game_loop::POST_MORTEM_TIME,
+    grid::Grid,
+    render::{Layout, Renderer},
+    replay::Replay,
+    themes::FullTheme,
+};
+
+/// Size of a terminal column in pixels
+const COLUMN_WIDTH: usize = 10;
+/// Size of a terminal row in pixels
+const ROW_HEIGHT: usize = 20;
+const FONT_SIZE: usize = 16;
+
+const BACKGROUND: &str = "#1e1e1e";
+const FOREGROUND: &str = "#d4d4d4";
+
+#[derive(Clone, Copy, Debug)]
+pub enum Format {
+    Svg,
+    Html,
+}
+
+impl Format {
+    /// Guesses the format from the extension of the file
+    pub fn from_path(path: &Path) -> eyre::Result<Self> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("svg") => Ok(Format::Svg),
+            Some(ext) if ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm") => {
+                Ok(Format::Html)
+            }
+            _ => eyre::bail!("Export file must have .svg or .html extension"),
+        }
+    }
+}
+
+/// Frames of the replay with the time each of them is shown.
+/// If `tick` is given, only the frame at that tick is returned.
+pub fn replay_frames(
+    replay: &Replay,
+    theme: &FullTheme,
+    tick: Option<u64>,
+) -> eyre::Result<Vec<(Grid, Duration)>> {
+    let mut game = GameState::new(replay.conf.clone());
+    let mut turns = replay.player();
+    let mut frames = Vec::new();
+
+    if tick == Some(0) {
+        return Ok(vec![(draw_frame(&game, theme), Duration::ZERO)]);
+    }
+
+    while game.tick() < replay.ticks {
+        if let Some((w, h)) = turns.resize_at(game.tick()) {
+            game.resize_field(w, h);
+        }
+        game.make_step(turns.turn_at(game.tick()));
+
+        // the final frame is shown longer, like after the game in the terminal
+        let over = matches!(game.status(), GameStatus::Dead | GameStatus::Win);
+        let frametime = match over {
+            true => POST_MORTEM_TIME,
+            false => game.expected_frametime(),
+        };
+        let frame = (draw_frame(&game, theme), frametime);
+        match tick {
+            Some(tick) if tick == game.tick() => return Ok(vec![frame]),
+            Some(_) => (),
+            None => frames.push(frame),
+        }
+
+        if over {
+            break;
+        }
+    }
+
+    if let Some(tick) = tick {
+        eyre::bail!(
+            "Replay ends at tick {}, there is no tick {tick}",
+            game.tick()
+        );
+    }
+
+    Ok(frames)
+}
+
+/// Draws the game the same way it is drawn in the terminal
+pub fn draw_frame(game: &GameState, theme: &FullTheme) -> Grid {
+    let (columns, rows) = Layout::new(theme).screen_size(game.width(), game.height());
+    let grid = Grid::new(columns, rows);
+
+    let mut renderer = Renderer::new(grid, game.width(), game.height(), theme);
+    renderer
+        .render_game(game, theme)
+        .expect("drawing into Grid cannot fail");
+    renderer.into_backend()
+}
+
+/// Writes `frames` as an SVG image, which is animated if there is more than one frame.
+/// HTML is a page with the SVG image embedded.
+pub fn export(
+    frames: &[(Grid, Duration)],
+    format: Format,
+    mut out: impl Write,
+) -> eyre::Result<()> {
+    let Some((first, _)) = frames.first() else {
+        eyre::bail!("Nothing to export");
+    };
+
+    // the field may be resized during the game, every frame gets the space of the largest one
+    let columns = frames
+        .iter()
+        .map(|(g, _)| g.columns())
+        .max()
+        .unwrap_or(first.columns());
+    let rows = frames
+        .iter()
+        .map(|(g, _)| g.rows())
+        .max()
+        .unwrap_or(first.rows());
+    let width = columns * COLUMN_WIDTH;
+    let height = rows * ROW_HEIGHT;
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+    )?;
+    writeln!(svg, "<style>")?;
+    writeln!(
+        svg,
+        "text {{ font-family: monospace; font-size: {FONT_SIZE}px; fill: {FOREGROUND}; white-space: pre; }}"
+    )?;
+    if frames.len() > 1 {
+        write_animation(&mut svg, frames, width)?;
+    }
+    writeln!(svg, "</style>")?;
+    writeln!(
+        svg,
+        r#"<rect width="100%" height="100%" fill="{BACKGROUND}"/>"#
+    )?;
+    writeln!(svg, r#"<g class="frames">"#)?;
+    for (i, (grid, _)) in frames.iter().enumerate() {
+        writeln!(svg, r#"<g transform="translate({})">"#, i * width)?;
+        write_grid(&mut svg, grid)?;
+        writeln!(svg, "</g>")?;
+    }
+    writeln!(svg, "</g>")?;
+    writeln!(svg, "</svg>")?;
+
+    match format {
+        Format::Svg => out.write_all(svg.as_bytes())?,
+        Format::Html => {
+            writeln!(out, "<!DOCTYPE html>")?;
+            writeln!(
+                out,
+                r#"<html><head><meta charset="utf-8"><title>snekgame</title></head>"#
+            )?;
+            writeln!(out, r#"<body style="background: {BACKGROUND}">"#)?;
+            out.write_all(svg.as_bytes())?;
+            writeln!(out, "</body></html>")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Frames are laid out horizontally and the animation
+/// moves them to the left, showing one frame at a time
+fn write_animation(
+    svg: &mut String,
+    frames: &[(Grid, Duration)],
+    width: usize,
+) -> std::fmt::Result {
+    let total: Duration = frames.iter().map(|(_, t)| *t).sum();
+    let total_secs = total.as_secs_f64().max(f64::EPSILON);
+
+    writeln!(
+        svg,
+        ".frames {{ animation: play {total_secs:.3}s step-end infinite; }}"
+    )?;
+    writeln!(svg, "@keyframes play {{")?;
+    let mut time = Duration::ZERO;
+    for (i, (_, frametime)) in frames.iter().enumerate() {
+        let percent = time.as_secs_f64() / total_secs * 100.0;
+        writeln!(
+            svg,
+            "  {percent:.4}% {{ transform: translateX(-{}px); }}",
+            i * width
+        )?;
+        time += *frametime;
+    }
+    writeln!(svg, "}}")
+}
+
+/// Writes every row of the grid as a `<text>` with the position of every character given,
+/// so wide characters and the font don't move anything out of place
+fn write_grid(svg: &mut String, grid: &Grid) -> std::fmt::Result {
+    for y in 0..grid.rows() {
+        let row = grid.row(y);
+        if row.iter().all(|c| c.text.trim().is_empty()) {
+            continue;
+        }
+
+        let mut xs = Vec::new();
+        let mut content = String::new();
+        let mut current_color = None;
+
+        for (x, cell) in row.iter().enumerate() {
+            if cell.text.is_empty() {
+                continue;
+            }
+            if cell.color != current_color {
+                if current_color.is_some() {
+                    content.push_str("</tspan>");
+                }
+                if let Some(color) = cell.color {
+                    write!(content, r#"<tspan fill="{}">"#, css_color(color))?;
+                }
+                current_color = cell.color;
+            }
+            for ch in cell.text.chars() {
+                xs.push((x * COLUMN_WIDTH).to_string());
+                push_escaped(&mut content, ch);
+            }
+        }
+        if current_color.is_some() {
+            content.push_str("</tspan>");
+        }
+
+        let baseline = y * ROW_HEIGHT + FONT_SIZE;
+        writeln!(
+            svg,
+            r#"<text x="{}" y="{baseline}">{content}</text>"#,
+            xs.join(" ")
+        )?;
+    }
+    Ok(())
+}
+
+fn push_escaped(s: &mut String, ch: char) {
+    match ch {
+        '&' => s.push_str("&amp;"),
+        '<' => s.push_str("&lt;"),
+        '>' => s.push_str("&gt;"),
+        ch => s.push(ch),
+    }
+}
+
+fn css_color(color: Color) -> String {
+    match color {
+        Color::Black | Color::DarkGrey => "#767676".to_owned(),
+        Color::Red => "#f14c4c".to_owned(),
+        Color::DarkRed => "#cd3131".to_owned(),
+        Color::Green => "#23d18b".to_owned(),
+        Color::DarkGreen => "#0dbc79".to_owned(),
+        Color::Yellow => "#f5f543".to_owned(),
+        Color::DarkYellow => "#e5e510".to_owned(),
+        Color::Blue => "#3b8eea".to_owned(),
+        Color::DarkBlue => "#2472c8".to_owned(),
+        Color::Magenta => "#d670d6".to_owned(),
+        Color::DarkMagenta => "#bc3fbc".to_owned(),
+        Color::Cyan => "#29b8db".to_owned(),
+        Color::DarkCyan => "#11a8cd".to_owned(),
+        Color::White | Color::Grey => "#e5e5e5".to_owned(),
+        Color::Rgb { r, g, b } => format!("#{r:02x}{g:02x}{b:02x}"),
+        Color::AnsiValue(_) | Color::Reset => FOREGROUND.to_owned(),
+    }
+}
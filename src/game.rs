@@ -1,11 +1,15 @@
 use std::{
     collections::{HashSet, VecDeque},
+    sync::Arc,
     time::Duration,
 };
 
 use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+use crate::ai::{self, Strategy};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Coords {
     pub x: usize,
     pub y: usize,
@@ -42,25 +46,9 @@ impl Coords {
         }
         Some(self)
     }
-
-    /// Compares two adjacent coords on `w` by `h` field, wrapping around the edges
-    pub fn compare(&self, other: &Coords, w: usize, h: usize) -> Dir {
-        match () {
-            _ if self.x == 0 && other.x == w - 1 => Dir::Right,
-            _ if self.x == w - 1 && other.x == 0 => Dir::Left,
-            _ if self.x > other.x => Dir::Right,
-            _ if self.x < other.x => Dir::Left,
-
-            _ if self.y == 0 && other.y == h - 1 => Dir::Down,
-            _ if self.y == h - 1 && other.y == 0 => Dir::Up,
-            _ if self.y > other.y => Dir::Down,
-            _ if self.y < other.y => Dir::Up,
-            _ => panic!("Looks like they have the same position: {self:?}, {other:?}"),
-        }
-    }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum Dir {
     // least significant bit represents direction (pos/neg),
@@ -72,27 +60,366 @@ pub enum Dir {
 }
 
 impl Dir {
+    pub const ALL: [Dir; 4] = [Dir::Left, Dir::Right, Dir::Up, Dir::Down];
+
+    pub fn opposite(self) -> Dir {
+        // flip the direction bit
+        match self as u8 ^ 0b01 {
+            0b00 => Dir::Left,
+            0b01 => Dir::Right,
+            0b10 => Dir::Up,
+            _ => Dir::Down,
+        }
+    }
+
     pub fn is_perpendicular(self, other: Dir) -> bool {
         // mask off axis bits and see if they are different
         (self as u8 & 0b10) != (other as u8 & 0b10)
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Food {
     pub pos: Coords,
     pub id: usize, // id is responsible for the type of food to render
+    /// Moving food moves every [`GameConf::food_move_every`] ticks
+    pub moving: bool,
+    /// Where the food was before it last moved, and the tick of the move
+    pub trail: Option<(Coords, u64)>,
 }
 
+/// What happens when the snake hits its own body
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum SelfHit {
+    /// The snake dies
+    #[default]
+    Die,
+    /// The snake loses everything from the hit point to the tail, and the score for it
+    Cut,
+    /// The snake goes through itself, like a ghost
+    Pass,
+}
+
+/// Where new food is placed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum FoodPlacement {
+    /// Anywhere on the field
+    #[default]
+    Uniform,
+    /// Not too close to the head of the snake
+    AwayFromHead,
+    /// Next to the edges of the field
+    NearWalls,
+    /// Close to the other food, or to where the last food was eaten
+    Clustered,
+    /// Only where the snake can get to, never enclosed by its body
+    Fair,
+}
+
+/// Largest width and height of the field. Configs come from game codes
+/// and replays too, and a huge field would take all the memory.
+pub const MAX_FIELD_SIZE: usize = 1000;
+
+/// Identifies a snake on the field, the player is [`PLAYER`] and rivals are numbered from 1
+pub type Owner = usize;
+pub const PLAYER: Owner = 0;
+
+/// Snake controlled by the computer
+#[derive(Clone, Debug)]
+pub struct Snake {
+    pub owner: Owner,
+    pub body: VecDeque<Coords>,
+    pub dir: Dir,
+    pub score: u32,
+}
+
+/// How the moving food moves
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum FoodMovement {
+    /// In random directions
+    #[default]
+    Random,
+    /// Away from the head of the snake
+    Flee,
+}
+
+/// The trail left by moving food is shown this long after the food moves
+pub const FOOD_TRAIL_TIME: Duration = Duration::from_millis(400);
+
+/// Cells within this distance from other food count as close for [`FoodPlacement::Clustered`]
+const CLUSTER_RADIUS: usize = 3;
+
+/// How the speed grows with the score.
+/// The speed goes up once every [`GameConf::food_to_speed_up`] points of score.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum SpeedCurve {
+    /// One move a second faster every time
+    #[default]
+    Linear,
+    /// A bit faster every time, and faster and faster
+    Exponential,
+    /// Much faster at first, and then less and less
+    Logarithmic,
+    /// Speeds of [`GameConf::speed_steps`]
+    Stepped,
+}
+
+/// Slowest speed, in moves a second
+pub const MIN_SPEED: f64 = 0.01;
+/// Fastest speed, in moves a second. The speed curves never go faster,
+/// e.g. the exponential one would reach infinity, which makes the steps take no time.
+pub const MAX_SPEED: f64 = 1000.0;
+
+/// Growth of the speed on every speed up with [`SpeedCurve::Exponential`]
+const EXPONENTIAL_GROWTH: f64 = 1.15;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GameConf {
     pub food_to_speed_up: u32,
     pub food_n: u32,
-    pub initial_speed: u32,
+    /// Moves a second at the start
+    pub initial_speed: f64,
     pub height: usize,
     pub width: usize,
     pub initial_length: usize,
     pub seed: u64,
     pub solid_walls: bool,
+    /// Number of portal pairs placed randomly on the field
+    #[serde(default)]
+    pub portals: u32,
+    #[serde(default)]
+    pub on_self_hit: SelfHit,
+    /// Number of times the snake can die before the game is over
+    #[serde(default = "one")]
+    pub lives: u32,
+    /// Cells the snake dies on, like on the walls
+    #[serde(default)]
+    pub obstacles: Vec<Coords>,
+    /// The game is won when the goal is reached
+    #[serde(default)]
+    pub goal: Option<Goal>,
+    #[serde(default)]
+    pub food_placement: FoodPlacement,
+    /// Number of food items that move
+    #[serde(default)]
+    pub moving_food: u32,
+    #[serde(default)]
+    pub food_movement: FoodMovement,
+    /// Moving food moves once in this many ticks
+    #[serde(default = "one")]
+    pub food_move_every: u32,
+    /// Number of computer controlled snakes
+    #[serde(default)]
+    pub rivals: u32,
+    #[serde(default)]
+    pub rival_strategy: Strategy,
+    #[serde(default)]
+    pub speed_curve: SpeedCurve,
+    /// Speeds of [`SpeedCurve::Stepped`]: from the score of each step, the snake
+    /// goes at its speed. Sorted by the score.
+    #[serde(default)]
+    pub speed_steps: Vec<(u32, f64)>,
+    /// The speed never gets higher than this
+    #[serde(default)]
+    pub max_speed: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Goal {
+    /// Eat this much food
+    EatFood(u32),
+    /// Grow the snake to this length
+    ReachLength(usize),
+    /// Stay alive for this many seconds
+    Survive(u32),
+    /// Reach this score
+    Score(u32),
+    /// Get as much score as possible in this many seconds
+    TimeAttack(u32),
+}
+
+impl Goal {
+    /// Time limit of the goal
+    pub fn duration(&self) -> Option<Duration> {
+        match *self {
+            Goal::Survive(secs) | Goal::TimeAttack(secs) => Some(Duration::from_secs(secs.into())),
+            Goal::EatFood(_) | Goal::ReachLength(_) | Goal::Score(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Goal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Goal::EatFood(n) => write!(f, "eat {n} food"),
+            Goal::ReachLength(n) => write!(f, "grow to length {n}"),
+            Goal::Survive(secs) => write!(f, "survive for {secs} seconds"),
+            Goal::Score(n) => write!(f, "reach score {n}"),
+            Goal::TimeAttack(secs) => write!(f, "get the highest score in {secs} seconds"),
+        }
+    }
+}
+
+fn one() -> u32 {
+    1
+}
+
+impl GameConf {
+    /// Checks that the game can be played with the config. Configs from game codes, replays
+    /// and other untrusted places must be checked before a game is created with them.
+    pub fn validate(&self) -> eyre::Result<()> {
+        let (w, h) = (self.width, self.height);
+        let cells = w.checked_mul(h);
+        let in_bounds = |speed: f64| (MIN_SPEED..=MAX_SPEED).contains(&speed);
+        match () {
+            _ if h == 0 => eyre::bail!("Height cannot be zero"),
+            _ if w == 0 => eyre::bail!("Width cannot be zero"),
+            _ if cells.is_none() || w > MAX_FIELD_SIZE || h > MAX_FIELD_SIZE => {
+                eyre::bail!("The field cannot be larger than {MAX_FIELD_SIZE}x{MAX_FIELD_SIZE}")
+            }
+            _ if !in_bounds(self.initial_speed) => {
+                eyre::bail!("Speed must be between {MIN_SPEED} and {MAX_SPEED}")
+                // TODO: when snake speed is 0 make the snake move only on input
+            }
+            _ if self.speed_curve == SpeedCurve::Stepped && self.speed_steps.is_empty() => {
+                eyre::bail!("Stepped speed curve needs at least one step")
+            }
+            _ if self.speed_steps.iter().any(|&(_, s)| !in_bounds(s)) => {
+                eyre::bail!("Speeds of the steps must be between {MIN_SPEED} and {MAX_SPEED}")
+            }
+            _ if self.max_speed.is_some_and(|s| !in_bounds(s)) => {
+                eyre::bail!("Maximum speed must be between {MIN_SPEED} and {MAX_SPEED}")
+            }
+            _ if self.max_speed.is_some_and(|s| s < self.initial_speed) => {
+                eyre::bail!("Speed cannot be higher than the maximum speed")
+            }
+            _ if self.goal == Some(Goal::Score(0)) => eyre::bail!("Goal score cannot be zero"),
+            _ if self.goal.and_then(|g| g.duration()) == Some(Duration::ZERO) => {
+                eyre::bail!("Time limit cannot be zero")
+            }
+            _ if self.food_move_every == 0 => eyre::bail!("Food cannot move every 0 steps"),
+            _ if self.lives == 0 => eyre::bail!("Number of lives cannot be zero"),
+            _ if self.initial_length == 0 => eyre::bail!("Snake length cannot be zero"),
+            _ if self.initial_length > w => {
+                eyre::bail!("Initial snake length cannot be larger then the width of the field")
+            }
+            _ if self.obstacles.len() > cells.unwrap_or(0) => {
+                eyre::bail!("There are more obstacles than cells on the field")
+            }
+            _ if self.obstacles.iter().any(|o| o.x >= w || o.y >= h) => {
+                eyre::bail!("Obstacles must be inside the field")
+            }
+            _ if self.start_snake().any(|s| self.obstacles.contains(&s)) => {
+                eyre::bail!("Obstacles cannot be where the snake starts")
+            }
+            // every piece is placed with a search of the field, so a config asking for far more
+            // pieces than fit would take forever to start
+            _ if (self.food_n as usize)
+                .saturating_add((self.rivals as usize).saturating_mul(self.initial_length))
+                .saturating_add(self.obstacles.len())
+                .saturating_add((self.portals as usize).saturating_mul(2))
+                > cells.unwrap_or(0) - self.initial_length =>
+            {
+                eyre::bail!(
+                    "There is not enough space on the field for all the food, rivals and portals"
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Where the snake of the player starts, from the head to the tail: in the middle of
+    /// the field, going right
+    pub fn start_snake(&self) -> impl Iterator<Item = Coords> {
+        let y = self.height / 2;
+        let head_x = (self.width - 1) / 2 + self.initial_length / 2;
+        let tail_x = head_x + 1 - self.initial_length;
+        (tail_x..=head_x).rev().map(move |x| Coords { x, y })
+    }
+
+    /// Moves a second the snake makes with `score`
+    pub fn speed_at(&self, score: u32) -> f64 {
+        let speed_ups = score.checked_div(self.food_to_speed_up).unwrap_or(0);
+        let initial = self.initial_speed;
+        let speed = match self.speed_curve {
+            SpeedCurve::Linear => initial + f64::from(speed_ups),
+            SpeedCurve::Exponential => initial * EXPONENTIAL_GROWTH.powf(f64::from(speed_ups)),
+            SpeedCurve::Logarithmic => initial + initial / 2.0 * f64::from(speed_ups).ln_1p(),
+            SpeedCurve::Stepped => match self.speed_steps.iter().rfind(|&&(s, _)| s <= score) {
+                Some(&(_, speed)) => speed,
+                None => initial,
+            },
+        };
+        speed.min(self.max_speed.unwrap_or(MAX_SPEED).min(MAX_SPEED))
+    }
+
+    /// Identifies the rules of the game for the high scores,
+    /// so only games with the same rules are compared. Seed is not included.
+    pub fn score_key(&self) -> String {
+        let mut key = format!(
+            "{}x{} speed:{}+1/{} length:{} food:{}",
+            self.width,
+            self.height,
+            self.initial_speed,
+            self.food_to_speed_up,
+            self.initial_length,
+            self.food_n,
+        );
+        if self.speed_curve != SpeedCurve::Linear {
+            let curve = format!("{:?}", self.speed_curve).to_lowercase();
+            key += &format!(" speed-curve:{curve}");
+        }
+        if self.speed_curve == SpeedCurve::Stepped {
+            let steps: Vec<String> = self
+                .speed_steps
+                .iter()
+                .map(|(score, speed)| format!("{score}:{speed}"))
+                .collect();
+            key += &format!(":{}", steps.join(","));
+        }
+        if let Some(max) = self.max_speed {
+            key += &format!(" max-speed:{max}");
+        }
+        if self.solid_walls {
+            key += " walls";
+        }
+        if self.portals != 0 {
+            key += &format!(" portals:{}", self.portals);
+        }
+        if self.on_self_hit != SelfHit::Die {
+            key += &format!(" self-hit:{:?}", self.on_self_hit).to_lowercase();
+        }
+        if self.lives != 1 {
+            key += &format!(" lives:{}", self.lives);
+        }
+        if !self.obstacles.is_empty() {
+            key += &format!(" obstacles:{}", self.obstacles.len());
+        }
+        if self.food_placement != FoodPlacement::Uniform {
+            let name = format!("{:?}", self.food_placement).to_lowercase();
+            key += &format!(" food-placement:{name}");
+        }
+        if self.moving_food != 0 {
+            let movement = format!("{:?}", self.food_movement).to_lowercase();
+            key += &format!(
+                " moving-food:{}/{}:{movement}",
+                self.moving_food, self.food_move_every
+            );
+        }
+        if self.rivals != 0 {
+            let strategy = format!("{:?}", self.rival_strategy).to_lowercase();
+            key += &format!(" rivals:{}:{strategy}", self.rivals);
+        }
+        match self.goal {
+            None => (),
+            Some(Goal::EatFood(n)) => key += &format!(" goal:food:{n}"),
+            Some(Goal::ReachLength(n)) => key += &format!(" goal:length:{n}"),
+            Some(Goal::Survive(secs)) => key += &format!(" goal:survive:{secs}"),
+            Some(Goal::Score(n)) => key += &format!(" goal:score:{n}"),
+            Some(Goal::TimeAttack(secs)) => key += &format!(" goal:time-attack:{secs}"),
+        }
+        key
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -100,46 +427,168 @@ pub enum GameStatus {
     Win,
     Dead,
     Ongoing,
+    /// The snake lost a life and has been placed back on the field.
+    /// It doesn't move until the countdown ends.
+    Respawning {
+        ticks_left: u32,
+    },
+}
+
+/// What the snake hit when it lost a life
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DeathCause {
+    /// Went into the solid wall from `pos`
+    Wall {
+        pos: Coords,
+    },
+    /// Hit its own body, `segment` is the index of the hit part counting from the head
+    #[serde(rename = "self")]
+    OwnBody {
+        pos: Coords,
+        segment: usize,
+    },
+    Obstacle {
+        pos: Coords,
+    },
+    /// Hit the body of the rival snake
+    Rival {
+        pos: Coords,
+        owner: Owner,
+    },
 }
 
+impl DeathCause {
+    /// The fatal cell: where the head was when it hit the wall, or what it hit
+    pub fn pos(&self) -> Coords {
+        match *self {
+            DeathCause::Wall { pos }
+            | DeathCause::OwnBody { pos, .. }
+            | DeathCause::Obstacle { pos }
+            | DeathCause::Rival { pos, .. } => pos,
+        }
+    }
+
+    /// Short name of the cause, without the details
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DeathCause::Wall { .. } => "wall",
+            DeathCause::OwnBody { .. } => "self",
+            DeathCause::Obstacle { .. } => "obstacle",
+            DeathCause::Rival { .. } => "rival",
+        }
+    }
+}
+
+impl std::fmt::Display for DeathCause {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Coords { x, y } = self.pos();
+        match self {
+            DeathCause::Wall { .. } => write!(f, "hit the wall at {x},{y}"),
+            DeathCause::OwnBody { segment, .. } => {
+                write!(f, "bit its own body at {x},{y} (segment {segment})")
+            }
+            DeathCause::Obstacle { .. } => write!(f, "hit an obstacle at {x},{y}"),
+            DeathCause::Rival { .. } => write!(f, "hit a rival snake at {x},{y}"),
+        }
+    }
+}
+
+/// How and when the snake lost a life
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Death {
+    pub cause: DeathCause,
+    pub tick: u64,
+}
+
+/// How long the snake waits after respawning
+const RESPAWN_TIME_SECS: u32 = 3;
+
+/// When respawning, this many cells in front of the snake must be free
+const RESPAWN_FREE_AHEAD: usize = 3;
+
 // TODO: we want to maintain some sort of 2d array with the board that represents
 // the current state of the game. It will store the postions of food,
 // for constant time lookup. And it will store all the snake pieces for the
 // renderer, so it doesn't need to reconstruct the board every frame.
+#[derive(Clone)]
 pub struct GameState {
-    conf: GameConf,
+    /// Shared by the copies of the state, it only changes when the field is resized
+    conf: Arc<GameConf>,
     snake: VecDeque<Coords>,
     snake_dir: Dir,
-    food: Vec<Food>,
+    rivals: Vec<Snake>,
+    /// Shared by the copies of the state until one of them changes it
+    food: Arc<Vec<Food>>,
+    /// Pairs of portal tiles. Entering one of them
+    /// moves the snake to the other one, keeping the direction.
+    /// They are placed at the start and shared by the copies of the state.
+    portals: Arc<Vec<(Coords, Coords)>>,
     status: GameStatus,
     score: u32,
-    speed: u32,
+    /// Moves a second
+    speed: f64,
+    lives_left: u32,
+    /// The last lost life
+    death: Option<Death>,
+    food_eaten: u32,
+    /// Game time, the sum of durations of all steps made
+    elapsed: Duration,
     rng: StdRng,
+    tick: u64, // number of steps made
 }
 
 impl GameState {
     pub fn new(conf: GameConf) -> Self {
-        let y = conf.height / 2;
-        let head_x = (conf.width - 1) / 2 + conf.initial_length / 2;
-        let tail_x = head_x + 1 - conf.initial_length;
-        let snake = (tail_x..=head_x).rev().map(|x| Coords { x, y }).collect();
-
-        let food = Vec::new();
+        let snake = conf.start_snake().collect();
 
         let mut game = GameState {
             snake,
             snake_dir: Dir::Right,
-            food,
+            rivals: Vec::new(),
+            food: Arc::default(),
+            portals: Arc::default(),
             status: GameStatus::Ongoing,
             score: 0,
-            speed: conf.initial_speed,
+            speed: conf.speed_at(0),
+            lives_left: conf.lives.max(1),
+            death: None,
+            food_eaten: 0,
+            elapsed: Duration::ZERO,
             rng: StdRng::seed_from_u64(conf.seed),
-            conf,
+            tick: 0,
+            conf: Arc::new(conf),
         };
 
-        for _ in 0..game.conf.food_n {
-            if let Some(new) = game.find_new_food_place() {
-                game.food.push(new);
+        for _ in 0..game.conf.portals {
+            let Some(a) = game.find_free_spot() else {
+                break;
+            };
+            // the first portal must be taken while looking for the second one
+            Arc::make_mut(&mut game.portals).push((a, a));
+            let Some(b) = game.find_free_spot() else {
+                Arc::make_mut(&mut game.portals).pop();
+                break;
+            };
+            Arc::make_mut(&mut game.portals).last_mut().unwrap().1 = b;
+        }
+
+        for owner in 1..=game.conf.rivals as Owner {
+            let Some((body, dir)) = game.find_snake_place(owner) else {
+                break;
+            };
+            game.rivals.push(Snake {
+                owner,
+                body,
+                dir,
+                score: 0,
+            });
+        }
+
+        for i in 0..game.conf.food_n {
+            if let Some(mut new) = game.find_new_food_place() {
+                new.moving = i < game.conf.moving_food;
+                Arc::make_mut(&mut game.food).push(new);
             } else {
                 game.status = GameStatus::Win;
                 break;
@@ -149,95 +598,489 @@ impl GameState {
         game
     }
 
+    /// Changes the size of the field to `width` by `height`, or as close to it as possible:
+    /// the field never shrinks so much that something on it would be left outside,
+    /// and never grows larger than [`MAX_FIELD_SIZE`].
+    /// Returns the new size.
+    pub fn resize_field(&mut self, width: usize, height: usize) -> (usize, usize) {
+        let taken = self
+            .snake
+            .iter()
+            .chain(self.rivals.iter().flat_map(|r| &r.body))
+            .chain(
+                self.food
+                    .iter()
+                    .flat_map(|f| [&f.pos].into_iter().chain(f.trail.as_ref().map(|(c, _)| c))),
+            )
+            .chain(self.portals.iter().flat_map(|(a, b)| [a, b]))
+            .chain(&self.conf.obstacles);
+        let (mut w, mut h) = (
+            width.clamp(1, MAX_FIELD_SIZE),
+            height.clamp(1, MAX_FIELD_SIZE),
+        );
+        for c in taken {
+            w = w.max(c.x + 1);
+            h = h.max(c.y + 1);
+        }
+
+        let conf = Arc::make_mut(&mut self.conf);
+        conf.width = w;
+        conf.height = h;
+        (w, h)
+    }
+
     pub fn make_step(&mut self, turn: Option<Dir>) {
+        self.tick += 1;
+        self.elapsed += self.expected_frametime();
+
+        self.move_snake(turn);
+        if let GameStatus::Ongoing | GameStatus::Respawning { .. } = self.status {
+            self.move_rivals();
+        }
+
+        let every = u64::from(self.conf.food_move_every.max(1));
+        if self.status == GameStatus::Ongoing && self.tick % every == 0 {
+            self.move_food();
+        }
+
+        if self.status != GameStatus::Dead && self.goal_reached() {
+            self.status = GameStatus::Win;
+        }
+    }
+
+    fn move_snake(&mut self, turn: Option<Dir>) {
+        if let GameStatus::Respawning { ticks_left } = &mut self.status {
+            *ticks_left -= 1;
+            if *ticks_left == 0 {
+                self.status = GameStatus::Ongoing;
+            }
+            return;
+        }
+
         if let Some(dir) = turn {
             self.snake_dir = dir;
         }
 
-        let new_head_pos = if self.conf.solid_walls {
-            match self.snake[0].move_bumping(self.snake_dir, self.conf.width, self.conf.height) {
-                Some(new_head_pos) => new_head_pos,
-                None => {
-                    self.status = GameStatus::Dead;
-                    return;
-                }
-            }
-        } else {
-            self.snake[0].move_wrapping(self.snake_dir, self.conf.width, self.conf.height)
+        let pos = self.snake[0];
+        let Some(new_head_pos) = self.neighbor(pos, self.snake_dir) else {
+            self.lose_life(DeathCause::Wall { pos });
+            return;
         };
+        let pos = new_head_pos;
+        if self.conf.obstacles.contains(&pos) {
+            self.lose_life(DeathCause::Obstacle { pos });
+            return;
+        }
+        if let Some(rival) = self.rivals.iter().find(|r| r.body.contains(&pos)) {
+            let owner = rival.owner;
+            self.lose_life(DeathCause::Rival { pos, owner });
+            return;
+        }
 
         // remember where was the tail, to place here new element if needed
         let old_tail = self.snake.pop_back().unwrap();
 
-        if self.snake.contains(&new_head_pos) {
-            self.status = GameStatus::Dead;
-            return;
+        if let Some(hit) = self.snake.iter().position(|&c| c == new_head_pos) {
+            match self.conf.on_self_hit {
+                SelfHit::Die => {
+                    // the snake doesn't move, so it is shown where it died
+                    self.snake.push_back(old_tail);
+                    let pos = new_head_pos;
+                    self.lose_life(DeathCause::OwnBody { pos, segment: hit });
+                    return;
+                }
+                SelfHit::Cut => {
+                    // the hit segment is where the head goes, so it is cut too
+                    let cut = self.snake.len() - hit;
+                    self.snake.truncate(hit);
+                    self.score = self.score.saturating_sub(cut as u32);
+                    self.update_speed();
+                }
+                SelfHit::Pass => (),
+            }
         }
 
         self.snake.push_front(new_head_pos);
 
         if let Some(i) = self.food.iter().position(|f| f.pos == new_head_pos) {
-            self.food.remove(i);
+            let eaten = Arc::make_mut(&mut self.food).remove(i);
             self.snake.push_back(old_tail);
 
-            if let Some(new) = self.find_new_food_place() {
-                self.food.push(new);
+            if let Some(mut new) = self.find_new_food_place() {
+                new.moving = eaten.moving;
+                Arc::make_mut(&mut self.food).push(new);
             } else {
                 self.status = GameStatus::Win;
             }
 
             self.score += 1;
-            if self.conf.food_to_speed_up != 0 {
-                // check for overflow, because initial_speed may be set by user to u32::MAX
-                self.speed = self
-                    .conf
-                    .initial_speed
-                    .saturating_add(self.score / self.conf.food_to_speed_up);
-            }
+            self.food_eaten += 1;
+            self.update_speed();
         }
     }
 
-    /// Finds new place for food, and
-    /// if there is no space on the field returns `None`
-    // TODO: make this function not have access to whole &mut self
-    fn find_new_food_place(&mut self) -> Option<Food> {
-        let mut taken_spots = HashSet::new();
-        taken_spots.extend(self.snake.iter().copied());
-        taken_spots.extend(self.food.iter().map(|f| f.pos));
+    /// Moves the rival snakes in the directions chosen by their strategy.
+    /// A rival that hits anything dies and is removed from the field.
+    fn move_rivals(&mut self) {
+        let mut i = 0;
+        while i < self.rivals.len() {
+            let dirs = ai::choose_dirs(self, &self.rivals[i], self.conf.rival_strategy);
+            let dir = match dirs.len() {
+                0 => self.rivals[i].dir,
+                n => dirs[self.rng.gen_range(0..n)],
+            };
 
-        let spots = self.conf.height * self.conf.width - taken_spots.len();
+            let rival = &mut self.rivals[i];
+            rival.dir = dir;
+            let head = rival.body[0];
+            // the tail moves away, so it can't be hit
+            let old_tail = rival.body.pop_back().unwrap();
 
-        if spots == 0 {
-            return None;
+            let next = self.neighbor(head, dir).filter(|next| {
+                !self.conf.obstacles.contains(next)
+                    && !self.snake.contains(next)
+                    && !self.rivals.iter().any(|r| r.body.contains(next))
+            });
+            let Some(next) = next else {
+                self.rivals.remove(i);
+                continue;
+            };
+
+            self.rivals[i].body.push_front(next);
+            if let Some(f) = self.food.iter().position(|f| f.pos == next) {
+                let eaten = Arc::make_mut(&mut self.food).remove(f);
+                let rival = &mut self.rivals[i];
+                rival.body.push_back(old_tail);
+                rival.score += 1;
+                if let Some(mut new) = self.find_new_food_place() {
+                    new.moving = eaten.moving;
+                    Arc::make_mut(&mut self.food).push(new);
+                }
+            }
+            i += 1;
         }
+    }
 
-        let choosen_spot = self.rng.gen_range(0..spots);
-        let mut coords = Coords { x: 0, y: 0 };
-        let mut i = 0;
-        loop {
-            if !taken_spots.contains(&coords) {
-                i += 1;
+    /// Moves every moving food item to a free neighboring cell, if there is one
+    fn move_food(&mut self) {
+        let w = self.conf.width;
+        let distances = match self.conf.food_movement {
+            FoodMovement::Random => Vec::new(),
+            FoodMovement::Flee => self.distances_from(self.snake[0]),
+        };
+        // unreachable cells are the best place to flee to
+        let distance = |c: Coords| distances[c.y * w + c.x].unwrap_or(usize::MAX);
+
+        for i in 0..self.food.len() {
+            if !self.food[i].moving {
+                continue;
             }
+            let pos = self.food[i].pos;
+            // food doesn't go through the portals
+            let free: Vec<Coords> = Dir::ALL
+                .into_iter()
+                .filter_map(|dir| {
+                    let (w, h) = (self.conf.width, self.conf.height);
+                    match self.conf.solid_walls {
+                        true => pos.move_bumping(dir, w, h),
+                        false => Some(pos.move_wrapping(dir, w, h)),
+                    }
+                })
+                .filter(|c| self.is_free(*c))
+                .collect();
 
-            if i > choosen_spot {
-                break;
+            let next = match self.conf.food_movement {
+                FoodMovement::Random if free.is_empty() => None,
+                FoodMovement::Random => Some(free[self.rng.gen_range(0..free.len())]),
+                FoodMovement::Flee => free
+                    .into_iter()
+                    .filter(|&c| distance(c) > distance(pos))
+                    .max_by_key(|&c| distance(c)),
+            };
+            if let Some(next) = next {
+                let food = &mut Arc::make_mut(&mut self.food)[i];
+                food.trail = Some((pos, self.tick));
+                food.pos = next;
+            }
+        }
+    }
+
+    fn is_free(&self, c: Coords) -> bool {
+        !self.is_blocked(c)
+            && !self.food.iter().any(|f| f.pos == c)
+            && !self.portals.iter().any(|&(a, b)| a == c || b == c)
+    }
+
+    /// True if a snake moving into `c` would die there
+    pub fn is_blocked(&self, c: Coords) -> bool {
+        self.snake.contains(&c)
+            || self.rivals.iter().any(|r| r.body.contains(&c))
+            || self.conf.obstacles.contains(&c)
+    }
+
+    fn goal_reached(&self) -> bool {
+        match self.conf.goal {
+            None => false,
+            Some(Goal::EatFood(n)) => self.food_eaten >= n,
+            Some(Goal::ReachLength(n)) => self.snake.len() >= n,
+            Some(Goal::Score(n)) => self.score >= n,
+            Some(goal @ (Goal::Survive(_) | Goal::TimeAttack(_))) => {
+                goal.duration().is_some_and(|limit| self.elapsed >= limit)
             }
+        }
+    }
 
-            coords.x += 1;
-            if coords.x >= self.conf.width {
-                coords.x = 0;
-                coords.y += 1;
+    /// Ends the game, or respawns the snake if it has lives left
+    fn lose_life(&mut self, cause: DeathCause) {
+        self.death = Some(Death {
+            cause,
+            tick: self.tick,
+        });
+        self.lives_left -= 1;
+        if self.lives_left == 0 {
+            self.status = GameStatus::Dead;
+            return;
+        }
+
+        let Some((snake, dir)) = self.find_snake_place(PLAYER) else {
+            self.lives_left = 0;
+            self.status = GameStatus::Dead;
+            return;
+        };
+        self.snake = snake;
+        self.snake_dir = dir;
+        self.status = GameStatus::Respawning {
+            ticks_left: ((self.speed * f64::from(RESPAWN_TIME_SECS)).round() as u32).max(1),
+        };
+    }
+
+    /// Searches the field for a place for a straight snake of initial length,
+    /// with free space in front of it. The player is placed close to the center,
+    /// and the rivals far from it. The current body of `owner` is not in the way.
+    fn find_snake_place(&self, owner: Owner) -> Option<(VecDeque<Coords>, Dir)> {
+        let (w, h) = (self.conf.width, self.conf.height);
+        let len = self.conf.initial_length;
+
+        let mut taken = HashSet::new();
+        taken.extend(self.food.iter().map(|f| f.pos));
+        taken.extend(self.portals.iter().flat_map(|&(a, b)| [a, b]));
+        taken.extend(self.conf.obstacles.iter().copied());
+        if owner != PLAYER {
+            taken.extend(self.snake.iter().copied());
+        }
+        for rival in self.rivals.iter().filter(|r| r.owner != owner) {
+            taken.extend(rival.body.iter().copied());
+        }
+
+        let center = Coords { x: w / 2, y: h / 2 };
+        let mut heads: Vec<Coords> = (0..h)
+            .flat_map(|y| (0..w).map(move |x| Coords { x, y }))
+            .collect();
+        heads.sort_by_key(|c| c.x.abs_diff(center.x) + c.y.abs_diff(center.y));
+        if owner != PLAYER {
+            heads.reverse();
+        }
+
+        for head in heads {
+            // the snake starts the game moving right, so it's tried first
+            'dirs: for dir in [Dir::Right, Dir::Left, Dir::Up, Dir::Down] {
+                // the body goes straight behind the head, not through portals
+                let mut snake = VecDeque::from([head]);
+                while snake.len() < len {
+                    let last = *snake.back().unwrap();
+                    let next = if self.conf.solid_walls {
+                        last.move_bumping(dir.opposite(), w, h)
+                    } else {
+                        Some(last.move_wrapping(dir.opposite(), w, h))
+                    };
+                    match next {
+                        Some(next) if !taken.contains(&next) && !snake.contains(&next) => {
+                            snake.push_back(next)
+                        }
+                        _ => continue 'dirs,
+                    }
+                }
+                if taken.contains(&head) {
+                    continue;
+                }
+
+                let mut ahead = head;
+                for _ in 0..RESPAWN_FREE_AHEAD {
+                    match self.neighbor(ahead, dir) {
+                        Some(next) if !snake.contains(&next) && !taken.contains(&next) => {
+                            ahead = next
+                        }
+                        _ => continue 'dirs,
+                    }
+                }
+
+                return Some((snake, dir));
             }
         }
 
+        None
+    }
+
+    fn update_speed(&mut self) {
+        self.speed = self.conf.speed_at(self.score);
+    }
+
+    /// Cell next to `pos` in `dir` direction, going through the portals.
+    /// `None` if it is outside the field with solid walls.
+    pub fn neighbor(&self, pos: Coords, dir: Dir) -> Option<Coords> {
+        let (w, h) = (self.conf.width, self.conf.height);
+        let next = if self.conf.solid_walls {
+            pos.move_bumping(dir, w, h)?
+        } else {
+            pos.move_wrapping(dir, w, h)
+        };
+
+        Some(self.portal_twin(next).unwrap_or(next))
+    }
+
+    /// Direction in which to move from `from` to get to its neighbor `to`
+    pub fn dir_to(&self, from: Coords, to: Coords) -> Option<Dir> {
+        Dir::ALL
+            .into_iter()
+            .find(|&dir| self.neighbor(from, dir) == Some(to))
+    }
+
+    /// The other end of the portal at `pos`, if there is a portal
+    fn portal_twin(&self, pos: Coords) -> Option<Coords> {
+        self.portals.iter().find_map(|&(a, b)| match () {
+            _ if a == pos => Some(b),
+            _ if b == pos => Some(a),
+            _ => None,
+        })
+    }
+
+    /// Finds new place for food according to the placement policy, and
+    /// if there is no space on the field returns `None`
+    fn find_new_food_place(&mut self) -> Option<Food> {
+        let free = self.free_cells();
+        if free.is_empty() {
+            return None;
+        }
+
+        let head = self.snake[0];
+        let (w, h) = (self.conf.width, self.conf.height);
+        let preferred: Vec<Coords> = match self.conf.food_placement {
+            FoodPlacement::Uniform => Vec::new(),
+            FoodPlacement::AwayFromHead => {
+                let min_distance = ((w + h) / 4).max(2);
+                let distances = self.distances_from(head);
+                free.iter()
+                    .copied()
+                    .filter(|c| distances[c.y * w + c.x].map_or(true, |d| d >= min_distance))
+                    .collect()
+            }
+            FoodPlacement::NearWalls => free
+                .iter()
+                .copied()
+                .filter(|c| c.x == 0 || c.y == 0 || c.x == w - 1 || c.y == h - 1)
+                .collect(),
+            FoodPlacement::Clustered => {
+                // the head is where the last food was eaten
+                let anchors: Vec<Coords> = match self.food.is_empty() {
+                    true if self.tick > 0 => vec![head],
+                    _ => self.food.iter().map(|f| f.pos).collect(),
+                };
+                free.iter()
+                    .copied()
+                    .filter(|c| {
+                        anchors
+                            .iter()
+                            .any(|a| a.x.abs_diff(c.x) + a.y.abs_diff(c.y) <= CLUSTER_RADIUS)
+                    })
+                    .collect()
+            }
+            FoodPlacement::Fair => {
+                let distances = self.distances_from(head);
+                free.iter()
+                    .copied()
+                    .filter(|c| distances[c.y * w + c.x].is_some())
+                    .collect()
+            }
+        };
+
+        // when no cell fits the policy, any free cell will do
+        let candidates = if preferred.is_empty() {
+            free
+        } else {
+            preferred
+        };
         Some(Food {
-            pos: coords,
+            pos: candidates[self.rng.gen_range(0..candidates.len())],
             id: self.rng.gen(),
+            moving: false,
+            trail: None,
         })
     }
 
+    /// Finds random cell not taken by anything, and
+    /// if there is no space on the field returns `None`
+    fn find_free_spot(&mut self) -> Option<Coords> {
+        let free = self.free_cells();
+        if free.is_empty() {
+            return None;
+        }
+        Some(free[self.rng.gen_range(0..free.len())])
+    }
+
+    /// Cells not taken by anything, row by row
+    fn free_cells(&self) -> Vec<Coords> {
+        let mut taken_spots = HashSet::new();
+        taken_spots.extend(self.snake.iter().copied());
+        taken_spots.extend(self.rivals.iter().flat_map(|r| r.body.iter().copied()));
+        taken_spots.extend(self.food.iter().map(|f| f.pos));
+        taken_spots.extend(self.portals.iter().flat_map(|&(a, b)| [a, b]));
+        taken_spots.extend(self.conf.obstacles.iter().copied());
+
+        (0..self.conf.height)
+            .flat_map(|y| (0..self.conf.width).map(move |x| Coords { x, y }))
+            .filter(|c| !taken_spots.contains(c))
+            .collect()
+    }
+
+    /// Number of steps the snake needs to get from `start` to every cell of the field,
+    /// indexed by `y * width + x`. `None` for cells it can't get to.
+    /// The body of the snake is in the way, except the tail, which moves away.
+    fn distances_from(&self, start: Coords) -> Vec<Option<usize>> {
+        let w = self.conf.width;
+        let mut blocked = HashSet::new();
+        blocked.extend(self.snake.iter().take(self.snake.len() - 1).copied());
+        blocked.extend(self.conf.obstacles.iter().copied());
+        blocked.extend(self.rivals.iter().flat_map(|r| r.body.iter().copied()));
+
+        let mut distances = vec![None; w * self.conf.height];
+        distances[start.y * w + start.x] = Some(0);
+        let mut queue = VecDeque::from([(start, 0)]);
+
+        while let Some((pos, distance)) = queue.pop_front() {
+            for dir in Dir::ALL {
+                let Some(next) = self.neighbor(pos, dir) else {
+                    continue;
+                };
+                let visited = &mut distances[next.y * w + next.x];
+                if visited.is_none() && !blocked.contains(&next) {
+                    *visited = Some(distance + 1);
+                    queue.push_back((next, distance + 1));
+                }
+            }
+        }
+        distances
+    }
+
     pub fn expected_frametime(&self) -> Duration {
-        Duration::from_secs(1) / self.speed
+        // the speed of a valid config is always in the bounds
+        let speed = self.speed.clamp(MIN_SPEED, MAX_SPEED);
+        Duration::try_from_secs_f64(1.0 / speed).unwrap_or(Duration::from_secs(1))
+    }
+    pub fn conf(&self) -> &GameConf {
+        &self.conf
+    }
+    pub fn tick(&self) -> u64 {
+        self.tick
     }
     pub fn width(&self) -> usize {
         self.conf.width
@@ -254,10 +1097,387 @@ impl GameState {
     pub fn status(&self) -> GameStatus {
         self.status
     }
-    pub fn snake_iter(&self) -> impl Iterator<Item = Coords> + '_ {
+    pub fn food_eaten(&self) -> u32 {
+        self.food_eaten
+    }
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+    /// Game time left until the time limit of the goal
+    pub fn time_left(&self) -> Option<Duration> {
+        let limit = self.conf.goal?.duration()?;
+        Some(limit.saturating_sub(self.elapsed))
+    }
+    pub fn lives_left(&self) -> u32 {
+        self.lives_left
+    }
+    /// When and how the snake lost its last life
+    pub fn last_death(&self) -> Option<Death> {
+        self.death
+    }
+    /// Whole seconds left until the respawned snake starts moving
+    pub fn respawn_countdown(&self) -> Option<u32> {
+        match self.status {
+            GameStatus::Respawning { ticks_left } => {
+                Some((f64::from(ticks_left) / self.speed).ceil() as u32)
+            }
+            _ => None,
+        }
+    }
+    pub fn snake_iter(&self) -> impl DoubleEndedIterator<Item = Coords> + Clone + '_ {
         self.snake.iter().copied()
     }
+    /// Copy of the state without the bodies of the snakes, which are put back with
+    /// [`GameState::set_snake`] and [`GameState::set_rival_bodies`]. Many states can be kept
+    /// this way, without a copy of the snakes in each.
+    pub(crate) fn clone_without_snakes(&self) -> Self {
+        let rivals = self
+            .rivals
+            .iter()
+            .map(|r| Snake {
+                body: VecDeque::new(),
+                ..*r
+            })
+            .collect();
+        Self {
+            conf: Arc::clone(&self.conf),
+            snake: VecDeque::new(),
+            snake_dir: self.snake_dir,
+            rivals,
+            food: Arc::clone(&self.food),
+            portals: Arc::clone(&self.portals),
+            status: self.status,
+            score: self.score,
+            speed: self.speed,
+            lives_left: self.lives_left,
+            death: self.death,
+            food_eaten: self.food_eaten,
+            elapsed: self.elapsed,
+            rng: self.rng.clone(),
+            tick: self.tick,
+        }
+    }
+    /// Replaces the snake, `snake` goes from the head to the tail
+    pub(crate) fn set_snake(&mut self, snake: impl IntoIterator<Item = Coords>) {
+        self.snake = snake.into_iter().collect();
+    }
+    /// Replaces the bodies of the rivals, in the order of [`GameState::rivals`]
+    pub(crate) fn set_rival_bodies(&mut self, bodies: impl IntoIterator<Item = VecDeque<Coords>>) {
+        for (rival, body) in self.rivals.iter_mut().zip(bodies) {
+            rival.body = body;
+        }
+    }
+    pub fn rivals(&self) -> &[Snake] {
+        &self.rivals
+    }
     pub fn food(&self) -> &[Food] {
         self.food.as_slice()
     }
+    /// Cells the moving food has just left, see [`FOOD_TRAIL_TIME`]
+    pub fn food_trails(&self) -> impl Iterator<Item = Coords> + '_ {
+        let ticks = (self.speed * FOOD_TRAIL_TIME.as_secs_f64()).ceil().max(1.0) as u64;
+        self.food
+            .iter()
+            .filter_map(move |f| f.trail.filter(|&(_, t)| self.tick - t < ticks))
+            .map(|(c, _)| c)
+    }
+    pub fn portals(&self) -> &[(Coords, Coords)] {
+        self.portals.as_slice()
+    }
+}
+
+/// Plain config for the tests of other modules: a walled field with one food and nothing else
+#[cfg(test)]
+pub(crate) fn test_conf() -> GameConf {
+    GameConf {
+        food_to_speed_up: 4,
+        food_n: 1,
+        initial_speed: 6.0,
+        height: 10,
+        width: 20,
+        initial_length: 3,
+        seed: 1,
+        solid_walls: true,
+        portals: 0,
+        on_self_hit: SelfHit::Die,
+        lives: 1,
+        obstacles: Vec::new(),
+        goal: None,
+        food_placement: FoodPlacement::Fair,
+        moving_food: 0,
+        food_movement: FoodMovement::Random,
+        food_move_every: 1,
+        rivals: 0,
+        rival_strategy: Strategy::Greedy,
+        speed_curve: SpeedCurve::Linear,
+        speed_steps: Vec::new(),
+        max_speed: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ValueEnum;
+
+    fn conf(food_placement: FoodPlacement, seed: u64) -> GameConf {
+        GameConf {
+            food_placement,
+            seed,
+            food_n: 3,
+            width: 12,
+            height: 8,
+            // the top left corner can't be reached
+            obstacles: vec![Coords { x: 1, y: 0 }, Coords { x: 0, y: 1 }],
+            ..test_conf()
+        }
+    }
+
+    fn food_positions(game: &GameState) -> Vec<Coords> {
+        game.food().iter().map(|f| f.pos).collect()
+    }
+
+    #[test]
+    fn food_placement() {
+        for seed in 0..50 {
+            let fair = GameState::new(conf(FoodPlacement::Fair, seed));
+            assert!(!food_positions(&fair).contains(&Coords { x: 0, y: 0 }));
+
+            let walls = GameState::new(conf(FoodPlacement::NearWalls, seed));
+            for c in food_positions(&walls) {
+                assert!(c.x == 0 || c.y == 0 || c.x == 11 || c.y == 7, "{c:?}");
+            }
+
+            let away = GameState::new(conf(FoodPlacement::AwayFromHead, seed));
+            let distances = away.distances_from(away.snake[0]);
+            for c in food_positions(&away) {
+                assert!(distances[c.y * 12 + c.x].unwrap() >= 5, "{c:?}");
+            }
+
+            let clustered = GameState::new(conf(FoodPlacement::Clustered, seed));
+            let food = food_positions(&clustered);
+            for (i, c) in food.iter().enumerate().skip(1) {
+                let close = food[..i]
+                    .iter()
+                    .any(|a| a.x.abs_diff(c.x) + a.y.abs_diff(c.y) <= CLUSTER_RADIUS);
+                assert!(close, "{food:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn moving_food() {
+        for food_movement in FoodMovement::value_variants() {
+            let mut game = GameState::new(GameConf {
+                moving_food: 2,
+                food_movement: *food_movement,
+                ..conf(FoodPlacement::Uniform, 3)
+            });
+            let mut moves = 0;
+            for _ in 0..8 {
+                let before = food_positions(&game);
+                game.make_step(None);
+                if game.status() != GameStatus::Ongoing {
+                    break;
+                }
+
+                for (i, food) in game.food().iter().enumerate() {
+                    assert_eq!(food.moving, i < 2);
+                    assert!(!game.snake_iter().any(|c| c == food.pos));
+                    assert!(!game.conf.obstacles.contains(&food.pos));
+                    if let Some((from, _)) = food.trail.filter(|&(_, t)| t == game.tick()) {
+                        moves += 1;
+                        assert_eq!(before[i], from);
+                        assert!(game.food_trails().any(|c| c == from));
+                        assert_eq!(from.x.abs_diff(food.pos.x) + from.y.abs_diff(food.pos.y), 1);
+                    }
+                }
+            }
+            assert!(moves > 0, "{food_movement:?}");
+        }
+    }
+
+    #[test]
+    fn rivals() {
+        for strategy in Strategy::value_variants() {
+            let mut game = GameState::new(GameConf {
+                rivals: 3,
+                rival_strategy: *strategy,
+                // the player keeps hitting the wall, while the rivals play
+                lives: 100,
+                ..conf(FoodPlacement::Uniform, 5)
+            });
+            assert_eq!(game.rivals().len(), 3);
+
+            let mut eaten = 0;
+            for _ in 0..60 {
+                game.make_step(None);
+                assert_ne!(game.status(), GameStatus::Dead);
+
+                let mut cells: Vec<Coords> = game.snake_iter().collect();
+                for rival in game.rivals() {
+                    cells.extend(rival.body.iter().copied());
+                    eaten = eaten.max(rival.score);
+                }
+                let count = cells.len();
+                cells.sort();
+                cells.dedup();
+                assert_eq!(cells.len(), count, "{strategy:?}: snakes overlap");
+            }
+            if *strategy != Strategy::Random {
+                assert!(eaten > 0, "{strategy:?}: rivals didn't eat anything");
+            }
+        }
+    }
+
+    #[test]
+    fn food_placement_is_deterministic() {
+        for placement in FoodPlacement::value_variants() {
+            let play = || {
+                let mut game = GameState::new(conf(*placement, 7));
+                let mut placed = Vec::new();
+                for turn in [None, Some(Dir::Up), None, Some(Dir::Left), None, None] {
+                    game.make_step(turn);
+                    placed.push(food_positions(&game));
+                }
+                placed
+            };
+            assert_eq!(play(), play(), "{placement:?}");
+        }
+    }
+
+    #[test]
+    fn field_resize_keeps_everything_inside() {
+        let mut game = GameState::new(conf(FoodPlacement::Uniform, 3));
+        assert_eq!(game.resize_field(30, 20), (30, 20));
+        assert_eq!(game.width(), 30);
+
+        let (w, h) = game.resize_field(1, 1);
+        for c in game.snake_iter().chain(game.food().iter().map(|f| f.pos)) {
+            assert!(c.x < w && c.y < h, "{c:?} outside {w}x{h}");
+        }
+        // the snake is in the middle of the original field
+        assert!(w > 1 && h > 1);
+    }
+
+    #[test]
+    fn untrusted_configs_are_rejected() {
+        assert!(test_conf().validate().is_ok());
+
+        let huge = |width, height| GameConf {
+            width,
+            height,
+            ..test_conf()
+        };
+        assert!(huge(5_000_000_000, 5_000_000_000).validate().is_err());
+        assert!(huge(MAX_FIELD_SIZE + 1, 10).validate().is_err());
+        assert!(huge(10, MAX_FIELD_SIZE + 1).validate().is_err());
+        assert!(huge(MAX_FIELD_SIZE, MAX_FIELD_SIZE).validate().is_ok());
+
+        let obstacles = GameConf {
+            obstacles: vec![Coords { x: 0, y: 0 }; 201],
+            ..test_conf()
+        };
+        assert!(obstacles.validate().is_err());
+
+        let portals = GameConf {
+            portals: u32::MAX,
+            ..test_conf()
+        };
+        assert!(portals.validate().is_err());
+
+        for initial_speed in [0.0, 0.001, -1.0, f64::NAN, f64::INFINITY, 1e9] {
+            let conf = GameConf {
+                initial_speed,
+                ..test_conf()
+            };
+            assert!(conf.validate().is_err(), "{initial_speed}");
+        }
+        // the pieces of a game must fit on the field, or placing them takes forever
+        let crowded = |food_n, rivals| GameConf {
+            food_n,
+            rivals,
+            ..huge(MAX_FIELD_SIZE, MAX_FIELD_SIZE)
+        };
+        assert!(crowded(1, 1000).validate().is_ok());
+        assert!(crowded(1_000_000, 0).validate().is_err());
+        assert!(crowded(1, 400_000).validate().is_err());
+        assert!(crowded(u32::MAX, u32::MAX).validate().is_err());
+
+        let start = test_conf().start_snake().last().unwrap();
+        let on_snake = GameConf {
+            obstacles: vec![start],
+            ..test_conf()
+        };
+        assert!(on_snake.validate().is_err());
+        let next_to_snake = GameConf {
+            obstacles: vec![Coords {
+                y: start.y + 1,
+                ..start
+            }],
+            ..test_conf()
+        };
+        assert!(next_to_snake.validate().is_ok());
+
+        for days in [0, 100, 10_000] {
+            let daily = crate::daily::conf(crate::daily::Date::from_days(days));
+            assert!(daily.validate().is_ok());
+        }
+
+        let faster_than_max = GameConf {
+            max_speed: Some(5.0),
+            ..test_conf()
+        };
+        assert!(faster_than_max.validate().is_err());
+    }
+
+    #[test]
+    fn speed_curves() {
+        let curve = |speed_curve, max_speed| GameConf {
+            speed_curve,
+            speed_steps: vec![(0, 2.5), (10, 5.0), (30, 9.0)],
+            max_speed,
+            ..conf(FoodPlacement::Uniform, 0)
+        };
+
+        let linear = curve(SpeedCurve::Linear, None);
+        assert_eq!(linear.speed_at(0), 6.0);
+        assert_eq!(linear.speed_at(9), 8.0);
+
+        let exponential = curve(SpeedCurve::Exponential, None);
+        assert!((exponential.speed_at(8) - 6.0 * 1.15 * 1.15).abs() < 1e-9);
+
+        let logarithmic = curve(SpeedCurve::Logarithmic, None);
+        assert!(logarithmic.speed_at(40) < linear.speed_at(40));
+        assert!(logarithmic.speed_at(40) > logarithmic.speed_at(20));
+
+        let stepped = curve(SpeedCurve::Stepped, None);
+        assert_eq!(stepped.speed_at(0), 2.5);
+        assert_eq!(stepped.speed_at(29), 5.0);
+        assert_eq!(stepped.speed_at(30), 9.0);
+
+        let capped = curve(SpeedCurve::Linear, Some(7.5));
+        assert_eq!(capped.speed_at(4), 7.0);
+        assert_eq!(capped.speed_at(8), 7.5);
+
+        // fractional speeds make fractional steps
+        let game = GameState::new(curve(SpeedCurve::Stepped, None));
+        assert_eq!(game.expected_frametime(), Duration::from_millis(400));
+
+        // the speed doesn't grow without a bound, and the steps never take no time
+        let exponential = GameConf {
+            food_to_speed_up: 1,
+            ..curve(SpeedCurve::Exponential, None)
+        };
+        assert_eq!(exponential.speed_at(u32::MAX), MAX_SPEED);
+        assert_eq!(curve(SpeedCurve::Linear, Some(1e9)).speed_at(0), 6.0);
+        for speed in [MIN_SPEED, MAX_SPEED, 1e-300, f64::NAN] {
+            let game = GameState::new(GameConf {
+                initial_speed: speed,
+                ..curve(SpeedCurve::Linear, None)
+            });
+            assert!(game.expected_frametime() > Duration::ZERO);
+            assert!(game.expected_frametime() <= Duration::from_secs(100));
+        }
+    }
 }
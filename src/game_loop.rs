@@ -1,70 +1,341 @@
 use crate::{
-    game::{GameState, GameStatus},
+    bot::Bot,
+    game::{GameState, GameStatus, Goal},
     input::{self, Input, InputBuffer},
-    render::Renderer,
+    practice::Practice,
+    render::{Backend, Layout, Renderer},
+    replay::Replay,
+    stats::{self, ThemeNames},
     themes::FullTheme,
 };
 
+use eyre::Context;
+
+use std::collections::VecDeque;
+use std::io::Write;
 use std::time::Duration;
 use std::time::Instant;
 
-struct Clock {
-    frame_start: Instant, // instant at which the current frame started
+/// Schedules something that happens periodically, like steps of the simulation or frames
+struct Ticker {
+    next: Instant,
+    /// If the ticker falls further behind than this many periods (e.g. the process
+    /// was suspended), the missed ticks are dropped instead of being made all at once
+    max_behind: u32,
 }
 
-impl Clock {
-    fn new() -> Self {
+impl Ticker {
+    fn new(first_in: Duration, max_behind: u32) -> Self {
         Self {
-            frame_start: Instant::now(),
+            next: Instant::now() + first_in,
+            max_behind,
         }
     }
 
-    fn frame_end(&mut self, expected_frametime: Duration) {
+    /// Instant when the next tick is due
+    fn next(&self) -> Instant {
+        self.next
+    }
+
+    /// Returns true if a tick is due now, and schedules the next one `period` later
+    fn due(&mut self, period: Duration) -> bool {
         let now = Instant::now();
-        let since_frame_start = now.duration_since(self.frame_start);
+        if now < self.next {
+            return false;
+        }
+
+        if now - self.next > period * self.max_behind {
+            self.next = now;
+        }
+        self.next += period;
+        true
+    }
+}
+
+/// Measures time between the player pressing a key to turn,
+/// and the step of the game in which the snake turns
+struct LatencyStats {
+    /// Instants at which the turns waiting in the input buffer were pressed
+    pending: VecDeque<Instant>,
+    samples: Vec<Duration>,
+}
+
+impl LatencyStats {
+    fn new() -> Self {
+        Self {
+            pending: VecDeque::new(),
+            samples: Vec::new(),
+        }
+    }
+
+    fn turn_buffered(&mut self, at: Instant) {
+        self.pending.push_back(at);
+    }
+
+    fn turn_made(&mut self) {
+        if let Some(pressed) = self.pending.pop_front() {
+            self.samples.push(pressed.elapsed());
+        }
+    }
+
+    fn report(mut self) {
+        if self.samples.is_empty() {
+            println!("No turns were made, nothing to report");
+            return;
+        }
+
+        self.samples.sort();
+        let percentile = |p: usize| self.samples[(self.samples.len() - 1) * p / 100];
+
+        println!("Input to step latency over {} turns:", self.samples.len());
+        for p in [50, 90, 99, 100] {
+            println!("  p{p:<3} {:>8.2?}", percentile(p));
+        }
+    }
+}
+
+/// How much holding the boost and brake keys changes the speed of the game
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpeedKeys {
+    /// The game goes this many times faster while the boost key is held
+    pub boost: f64,
+    /// The game goes this many times slower while the brake key is held, no brake if `None`
+    pub brake: Option<f64>,
+}
+
+impl Default for SpeedKeys {
+    fn default() -> Self {
+        Self {
+            boost: 2.0,
+            brake: None,
+        }
+    }
+}
+
+/// Terminals that don't report key releases repeat the presses while the key is held,
+/// so without releases the key counts as held this long after the last press.
+/// The first repeat only comes after the autorepeat delay, which is 660 ms by default
+/// on X11, so it's longer than that, or the key would count as released until the repeats start.
+const HOLD_WITHOUT_RELEASE: Duration = Duration::from_millis(750);
+
+/// Whether a key is held down
+#[derive(Default)]
+struct HeldKey {
+    pressed: bool,
+    last_press: Option<Instant>,
+}
+
+impl HeldKey {
+    fn update(&mut self, pressed: bool) {
+        self.pressed = pressed;
+        if pressed {
+            self.last_press = Some(Instant::now());
+        }
+    }
+
+    fn is_held(&self, key_release: bool) -> bool {
+        match key_release {
+            true => self.pressed,
+            false => self
+                .last_press
+                .is_some_and(|t| t.elapsed() < HOLD_WITHOUT_RELEASE),
+        }
+    }
+}
+
+/// How long the final frame, with the fatal cell highlighted, is shown after the snake dies
+pub const POST_MORTEM_TIME: Duration = Duration::from_millis(1500);
+
+/// Shown after the snake dies in practice mode, while the game waits for a rewind
+const PRACTICE_HINT: &str = "Press R to rewind, Q to quit";
 
-        if since_frame_start >= expected_frametime {
-            //self.dt = since_frame_start;
-            self.frame_start = now;
-        } else {
-            std::thread::sleep(expected_frametime - since_frame_start);
-            let after_sleep = Instant::now();
+/// Shows the final frame of the game, until the time runs out or the player quits
+fn post_mortem(
+    renderer: &mut Renderer<impl Backend>,
+    game: &GameState,
+    theme: &FullTheme,
+) -> eyre::Result<()> {
+    renderer.render_game(game, theme)?;
 
-            //self.dt = after_sleep.duration_since(self.frame_start);
-            self.frame_start = after_sleep;
+    let until = Instant::now() + POST_MORTEM_TIME;
+    loop {
+        let timeout = until.saturating_duration_since(Instant::now());
+        if timeout.is_zero() {
+            return Ok(());
+        }
+        match input::wait_input(timeout)? {
+            Some(Input::Resize(columns, rows)) => {
+                renderer.resize(columns, rows)?;
+                renderer.render_game(game, theme)?;
+            }
+            Some(Input::Quit) => return Ok(()),
+            Some(_) | None => (),
         }
     }
 }
 
-pub fn run(mut game: GameState, theme: &FullTheme) -> eyre::Result<()> {
-    let mut renderer = Renderer::init(game.width(), game.height())?;
+pub struct RunOptions<'a> {
+    /// Replay to play instead of taking the input from the player
+    pub playback: Option<&'a Replay>,
+    /// Bot that plays instead of the player
+    pub bot: Option<&'a mut Bot>,
+    /// Practice mode, in which the game can be rewound
+    pub practice: Option<&'a mut Practice>,
+    /// Where to write the asciicast recording of the game
+    pub asciicast: Option<Box<dyn Write>>,
+    /// How many times a second the screen is redrawn
+    pub frame_rate: u32,
+    /// Report input latency percentiles after the game
+    pub measure_latency: bool,
+    /// Resize the field to fill the terminal whenever the terminal is resized
+    pub fit_field: bool,
+    pub speed_keys: SpeedKeys,
+    /// Themes to add the game to the lifetime stats with, the game is not added if `None`
+    pub stats: Option<ThemeNames>,
+}
+
+/// Runs the game in the terminal, returns the final state and the replay of the game
+pub fn run(
+    mut game: GameState,
+    theme: &FullTheme,
+    options: RunOptions,
+) -> eyre::Result<(GameState, Replay)> {
+    let mut renderer = Renderer::init(game.width(), game.height(), theme, options.asciicast)?;
+
+    let mut replay = Replay::new(game.conf().clone());
+    let mut playback = options.playback.map(|r| (r.player(), r.ticks));
+    let mut bot = options.bot;
+    let mut practice = options.practice;
 
     let mut input_buf = InputBuffer::new();
 
-    let mut clock = Clock::new();
+    let mut latency = LatencyStats::new();
+
+    let key_release = renderer.backend_mut().reports_key_release();
+    let (mut boost, mut brake) = (HeldKey::default(), HeldKey::default());
+    let speed_keys = options.speed_keys;
+    // duration of the next step, with the boost and the brake
+    let step_time = |game: &GameState, boost: &HeldKey, brake: &HeldKey| {
+        let mut rate = 1.0;
+        if boost.is_held(key_release) {
+            rate *= speed_keys.boost;
+        }
+        if let Some(brake_factor) = speed_keys.brake.filter(|_| brake.is_held(key_release)) {
+            rate /= brake_factor;
+        }
+        game.expected_frametime().div_f64(rate)
+    };
+
+    let frametime = Duration::from_secs(1) / options.frame_rate;
+    let mut frame_ticker = Ticker::new(Duration::ZERO, 0);
+    let mut step_ticker = Ticker::new(game.expected_frametime(), 4);
 
     'game_loop: loop {
-        while let Some(event) = input::get_input()? {
+        // in practice mode the game waits after the death for the player to rewind
+        let stepping = game.status() != GameStatus::Dead;
+
+        // wait for input until there is something else to do,
+        // so the input is handled as soon as it arrives
+        let next = match stepping {
+            true => frame_ticker.next().min(step_ticker.next()),
+            false => frame_ticker.next(),
+        };
+        let timeout = next.saturating_duration_since(Instant::now());
+
+        if let Some(event) = input::wait_input(timeout)? {
             match event {
                 Input::_Pause => todo!(),
-                Input::Resize => renderer.queue_clear(),
-                Input::Move(dir) => input_buf.buffer_input(&game, dir),
+                Input::Resize(columns, rows) => {
+                    renderer.resize(columns, rows)?;
+                    if options.fit_field && playback.is_none() {
+                        let (w, h) = Layout::new(theme)
+                            .max_field_size(usize::from(columns), usize::from(rows));
+                        let old = (game.width(), game.height());
+                        let (w, h) = game.resize_field(w, h);
+                        if (w, h) != old {
+                            replay.record_resize(game.tick(), w, h);
+                        }
+                    }
+                }
+                Input::Move(dir) if playback.is_none() && bot.is_none() => {
+                    if input_buf.buffer_input(&game, dir) {
+                        latency.turn_buffered(Instant::now());
+                    }
+                }
+                Input::Move(_) => (),
+                Input::Rewind => {
+                    if let Some(state) = practice.as_deref_mut().and_then(Practice::rewind) {
+                        game = state;
+                        replay.rewind_to(game.tick());
+                        input_buf.clear();
+                        latency.pending.clear();
+                        step_ticker = Ticker::new(game.expected_frametime(), 4);
+                    }
+                }
+                Input::Boost(pressed) => boost.update(pressed),
+                Input::Brake(pressed) => brake.update(pressed),
                 Input::Quit => break 'game_loop,
             }
         }
 
-        game.make_step(input_buf.turn_to_do());
+        while game.status() != GameStatus::Dead && step_ticker.due(step_time(&game, &boost, &brake))
+        {
+            if let Some((w, h)) = playback
+                .as_mut()
+                .and_then(|(p, _)| p.resize_at(game.tick()))
+            {
+                game.resize_field(w, h);
+            }
+            let turn = match &mut playback {
+                Some((_, ticks)) if game.tick() >= *ticks => break 'game_loop,
+                Some((player, _)) => player.turn_at(game.tick()),
+                None => match bot.as_deref_mut().map(|bot| bot.next_turn(&game)) {
+                    Some(Err(_)) => break 'game_loop,
+                    Some(Ok(turn)) => turn.filter(|_| game.status() == GameStatus::Ongoing),
+                    // turns made during the countdown would be applied
+                    // to the snake that died, not the respawned one
+                    None if matches!(game.status(), GameStatus::Respawning { .. }) => {
+                        input_buf.clear();
+                        latency.pending.clear();
+                        None
+                    }
+                    None => input_buf.turn_to_do(),
+                },
+            };
+            if let Some(dir) = turn {
+                replay.record_turn(game.tick(), dir);
+                latency.turn_made();
+            }
+
+            if let Some(practice) = practice.as_deref_mut() {
+                practice.record(&game);
+            }
+            game.make_step(turn);
 
-        match game.status() {
-            GameStatus::Dead | GameStatus::Win => break,
-            GameStatus::Ongoing => (),
+            match game.status() {
+                GameStatus::Dead if practice.is_some() => break,
+                GameStatus::Dead | GameStatus::Win => break 'game_loop,
+                GameStatus::Ongoing | GameStatus::Respawning { .. } => (),
+            }
         }
 
-        renderer.render_game(&game, theme)?;
+        if frame_ticker.due(frametime) {
+            let waiting = practice.is_some() && game.status() == GameStatus::Dead;
+            renderer.set_hint(waiting.then_some(PRACTICE_HINT));
+            renderer.render_game(&game, theme)?;
+        }
+    }
 
-        clock.frame_end(game.expected_frametime());
+    // in practice mode the final frame was already shown while waiting for a rewind
+    if game.status() == GameStatus::Dead && practice.is_none() {
+        post_mortem(&mut renderer, &game, theme)?;
     }
 
+    replay.finish(game.tick());
+    renderer
+        .backend_mut()
+        .finish_recording()
+        .wrap_err("Unable to write the asciicast recording")?;
     drop(renderer);
 
     // the renderer does not print a new line after last line,
@@ -72,9 +343,52 @@ pub fn run(mut game: GameState, theme: &FullTheme) -> eyre::Result<()> {
     // empty line at the bottom of the screen
     println!();
 
+    if let (GameStatus::Dead, Some(death)) = (game.status(), game.last_death()) {
+        println!(
+            "Game over: the snake {} on tick {}",
+            death.cause, death.tick
+        );
+    }
+
     if game.status() == GameStatus::Win {
-        println!("You Won!");
+        let secs = game.elapsed().as_secs_f64();
+        match game.conf().goal {
+            Some(Goal::TimeAttack(_)) => println!("Time's up! Score: {}", game.score()),
+            Some(_) => println!("You Won! Goal completed in {secs:.1}s"),
+            None => println!("You Won!"),
+        }
     }
 
-    Ok(())
+    if options.measure_latency {
+        latency.report();
+    }
+
+    if let Some(themes) = &options.stats {
+        stats::record_game(&game, themes);
+    }
+
+    Ok((game, replay))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn held_keys() {
+        let mut key = HeldKey::default();
+        assert!(!key.is_held(true));
+        assert!(!key.is_held(false));
+
+        key.update(true);
+        assert!(key.is_held(true));
+        assert!(key.is_held(false));
+
+        // without releases the key is held until the presses stop repeating
+        key.update(false);
+        assert!(!key.is_held(true));
+        assert!(key.is_held(false));
+        key.last_press = Instant::now().checked_sub(HOLD_WITHOUT_RELEASE);
+        assert!(!key.is_held(false));
+    }
 }
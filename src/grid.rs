@@ -0,0 +1,127 @@
+use crossterm::style::Color;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::render::Backend;
+
+/// One column of the [`Grid`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GridCell {
+    /// Text in the column. Empty if the column is covered by a wide character to the left.
+    pub text: String,
+    pub color: Option<Color>,
+}
+
+impl GridCell {
+    fn blank() -> Self {
+        Self {
+            text: " ".to_owned(),
+            color: None,
+        }
+    }
+}
+
+/// Screen of text with fixed size, that works like a terminal without one
+#[derive(Clone, Debug)]
+pub struct Grid {
+    columns: usize,
+    cells: Vec<GridCell>,
+    cursor: (usize, usize),
+}
+
+impl Grid {
+    pub fn new(columns: usize, rows: usize) -> Self {
+        Self {
+            columns,
+            cells: vec![GridCell::blank(); columns * rows],
+            cursor: (0, 0),
+        }
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.cells.len().checked_div(self.columns).unwrap_or(0)
+    }
+
+    pub fn row(&self, y: usize) -> &[GridCell] {
+        &self.cells[y * self.columns..(y + 1) * self.columns]
+    }
+
+    /// Writes `s` starting at column `x` of row `y`, clipping what doesn't fit.
+    /// Returns the number of columns `s` takes.
+    pub fn put(&mut self, x: usize, y: usize, s: &str, color: Option<Color>) -> usize {
+        self.write(x, y, s, color, usize::MAX);
+        UnicodeWidthStr::width(s)
+    }
+
+    fn write(&mut self, mut x: usize, y: usize, s: &str, color: Option<Color>, columns: usize) {
+        let limit = x.saturating_add(columns).min(self.columns);
+        let row = y * self.columns;
+        let mut last: Option<usize> = None;
+
+        for ch in s.chars() {
+            let width = ch.width().unwrap_or(0);
+
+            if width == 0 {
+                // zero width characters are combined with the previous one
+                if let Some(i) = last {
+                    self.cells[i].text.push(ch);
+                }
+                continue;
+            }
+            if x + width > limit {
+                break;
+            }
+
+            self.cells[row + x] = GridCell {
+                text: ch.to_string(),
+                color,
+            };
+            for covered in 1..width {
+                self.cells[row + x + covered] = GridCell {
+                    text: String::new(),
+                    color,
+                };
+            }
+            last = Some(row + x);
+            x += width;
+        }
+    }
+}
+
+impl Backend for Grid {
+    fn move_to(&mut self, x: u16, y: u16) -> std::io::Result<()> {
+        self.cursor = (x as usize, y as usize);
+        Ok(())
+    }
+
+    fn print(&mut self, s: &str, color: Option<Color>) -> std::io::Result<()> {
+        let (x, y) = self.cursor;
+        if y < self.rows() {
+            self.cursor.0 += self.put(x, y, s, color);
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) -> std::io::Result<()> {
+        self.cells.fill(GridCell::blank());
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Text of the grid without colors, with trailing spaces of the rows trimmed
+impl std::fmt::Display for Grid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for y in 0..self.rows() {
+            let line: String = self.row(y).iter().map(|c| c.text.as_str()).collect();
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
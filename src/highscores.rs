@@ -0,0 +1,197 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use eyre::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::{DeathCause, GameState, GameStatus},
+    storage,
+};
+
+/// Number of best scores kept for every game config
+const TABLE_SIZE: usize = 10;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub score: u32,
+    pub length: usize,
+    /// Game time in seconds the game lasted
+    #[serde(default)]
+    pub time: f64,
+    /// Seconds since the unix epoch
+    pub timestamp: u64,
+    /// How the game ended, if the snake died
+    #[serde(default)]
+    pub death: Option<DeathCause>,
+    /// The game was rewound in practice mode. Unranked entries have their own
+    /// places in the table, and are never the best score.
+    #[serde(default)]
+    pub unranked: bool,
+}
+
+impl Entry {
+    pub fn from_game(game: &GameState) -> Self {
+        Self {
+            score: game.score(),
+            length: game.snake_iter().count(),
+            time: game.elapsed().as_secs_f64(),
+            death: match game.status() {
+                GameStatus::Dead => game.last_death().map(|d| d.cause),
+                _ => None,
+            },
+            unranked: false,
+            timestamp: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }
+    }
+}
+
+/// Best scores, stored separately for every game config,
+/// since scores of different configs can't be compared
+#[derive(Default, Serialize, Deserialize)]
+pub struct HighScores {
+    tables: BTreeMap<String, Vec<Entry>>,
+    /// The first attempt of every daily challenge, by date
+    #[serde(default)]
+    daily: BTreeMap<String, Entry>,
+}
+
+impl HighScores {
+    /// Default location of the high scores file
+    pub fn default_path() -> Option<PathBuf> {
+        Some(dirs::data_dir()?.join("snekgame").join("highscores.json"))
+    }
+
+    /// Loads high scores, if the file doesn't exist yet there are no high scores
+    pub fn load(path: &Path) -> eyre::Result<Self> {
+        storage::load_json(path).wrap_err("Unable to load high scores")
+    }
+
+    pub fn save(&self, path: &Path) -> eyre::Result<()> {
+        storage::save_json(path, self).wrap_err("Unable to save high scores")
+    }
+
+    /// Adds the entry into the table for `key`. Returns the place it took among
+    /// the entries that are ranked the same way, or `None` if it is not good enough.
+    pub fn add(&mut self, key: String, entry: Entry) -> Option<usize> {
+        let table = self.tables.entry(key).or_default();
+
+        // with the same score the faster game is better,
+        // later entries with the same score and time go below the earlier ones
+        let index = table.partition_point(|e| {
+            e.score > entry.score || (e.score == entry.score && e.time <= entry.time)
+        });
+        let place = table[..index]
+            .iter()
+            .filter(|e| e.unranked == entry.unranked)
+            .count();
+        if place >= TABLE_SIZE {
+            return None;
+        }
+
+        table.insert(index, entry);
+        let mut kept = [0, 0];
+        table.retain(|e| {
+            let kept = &mut kept[usize::from(e.unranked)];
+            *kept += 1;
+            *kept <= TABLE_SIZE
+        });
+        Some(place)
+    }
+
+    pub fn best(&self, key: &str) -> Option<&Entry> {
+        self.tables.get(key)?.iter().find(|e| !e.unranked)
+    }
+
+    pub fn daily(&self, date: &str) -> Option<&Entry> {
+        self.daily.get(date)
+    }
+
+    /// Records the daily challenge attempt, only the first one of the day counts.
+    /// Returns true if it was recorded.
+    pub fn add_daily(&mut self, date: String, entry: Entry) -> bool {
+        if self.daily.contains_key(&date) {
+            return false;
+        }
+        self.daily.insert(date, entry);
+        true
+    }
+}
+
+/// Adds the result of the game to the high scores and tells the player how they did.
+/// `unranked` games are recorded, but don't compete with the others.
+/// If the high scores can't be loaded or saved, the player is warned and nothing else happens.
+pub fn record_game(game: &GameState, unranked: bool) {
+    if let Err(e) = try_record_game(game, unranked) {
+        eprintln!("Unable to save the high score: {e:#}");
+    }
+}
+
+fn try_record_game(game: &GameState, unranked: bool) -> eyre::Result<()> {
+    let Some(path) = HighScores::default_path() else {
+        return Ok(());
+    };
+    if game.score() == 0 {
+        return Ok(());
+    }
+
+    let mut scores = HighScores::load(&path)?;
+    let key = game.conf().score_key();
+    let previous_best = scores.best(&key).map(|e| e.score);
+
+    let entry = Entry {
+        unranked,
+        ..Entry::from_game(game)
+    };
+    scores.add(key, entry);
+    scores.save(&path)?;
+
+    if unranked {
+        println!("The game was rewound, so it is unranked");
+        return Ok(());
+    }
+
+    match previous_best {
+        Some(best) if best >= game.score() => println!("High score: {best}"),
+        _ => println!("New high score: {}!", game.score()),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(score: u32, unranked: bool) -> Entry {
+        Entry {
+            score,
+            length: 3,
+            time: 10.0,
+            timestamp: 0,
+            death: None,
+            unranked,
+        }
+    }
+
+    #[test]
+    fn unranked_entries_do_not_compete() {
+        let mut scores = HighScores::default();
+        let key = "key".to_string();
+        for score in 1..=TABLE_SIZE as u32 {
+            scores.add(key.clone(), entry(score, false));
+        }
+        assert_eq!(scores.add(key.clone(), entry(100, true)), Some(0));
+        assert_eq!(scores.add(key.clone(), entry(0, true)), Some(1));
+        assert_eq!(scores.add(key.clone(), entry(0, false)), None);
+
+        assert_eq!(scores.best(&key).unwrap().score, TABLE_SIZE as u32);
+        assert_eq!(scores.tables[&key].len(), TABLE_SIZE + 2);
+    }
+}
@@ -9,6 +9,12 @@ pub struct InputBuffer {
     internal: [Dir; Self::CAP as usize],
 }
 
+impl Default for InputBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl InputBuffer {
     const CAP: u8 = 5;
 
@@ -27,12 +33,14 @@ impl InputBuffer {
         self.as_slice().last().copied()
     }
 
-    /// Pushes element if buffer is not full
-    fn enqueue(&mut self, input: Dir) {
+    /// Pushes element if buffer is not full, returns true if it was pushed
+    fn enqueue(&mut self, input: Dir) -> bool {
         if self.size < Self::CAP {
             self.internal[self.size as usize] = input;
             self.size += 1;
+            return true;
         }
+        false
     }
 
     fn dequeue(&mut self) -> Option<Dir> {
@@ -47,12 +55,16 @@ impl InputBuffer {
         Some(first)
     }
 
-    pub fn buffer_input(&mut self, game: &GameState, input: Dir) {
+    /// Returns true if the input was buffered
+    pub fn buffer_input(&mut self, game: &GameState, input: Dir) -> bool {
         let current_dir = self.last().unwrap_or(game.snake_dir());
 
-        if input.is_perpendicular(current_dir) {
-            self.enqueue(input);
-        }
+        input.is_perpendicular(current_dir) && self.enqueue(input)
+    }
+
+    /// Drops all buffered turns
+    pub fn clear(&mut self) {
+        self.size = 0;
     }
 
     pub fn turn_to_do(&mut self) -> Option<Dir> {
@@ -64,14 +76,20 @@ impl InputBuffer {
 
 pub enum Input {
     _Pause,
-    Resize,
+    Resize(u16, u16),
     Move(Dir),
+    /// Go back a few ticks, in practice mode
+    Rewind,
+    /// The boost key is pressed (true), or released (false)
+    Boost(bool),
+    /// The brake key is pressed (true), or released (false)
+    Brake(bool),
     Quit,
 }
 
 fn handle_event(e: &Event) -> Option<Input> {
     match e {
-        Event::Resize(_, _) => Some(Input::Resize),
+        Event::Resize(columns, rows) => Some(Input::Resize(*columns, *rows)),
         Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
             KeyCode::Char('w') | KeyCode::Up => Some(Input::Move(Dir::Up)),
             KeyCode::Char('s') | KeyCode::Down => Some(Input::Move(Dir::Down)),
@@ -80,18 +98,83 @@ fn handle_event(e: &Event) -> Option<Input> {
             KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                 Some(Input::Quit)
             }
+            KeyCode::Char('r') | KeyCode::Backspace => Some(Input::Rewind),
+            KeyCode::Char(' ') => Some(Input::Boost(true)),
+            KeyCode::Char('b') => Some(Input::Brake(true)),
             KeyCode::Char('q') => Some(Input::Quit),
             _ => None,
         },
+        // repeats and releases are only reported with keyboard enhancement, see `AnsiBackend`
+        Event::Key(key) if key.kind == KeyEventKind::Repeat => match key.code {
+            KeyCode::Char(' ') => Some(Input::Boost(true)),
+            KeyCode::Char('b') => Some(Input::Brake(true)),
+            _ => None,
+        },
+        Event::Key(key) if key.kind == KeyEventKind::Release => match key.code {
+            KeyCode::Char(' ') => Some(Input::Boost(false)),
+            KeyCode::Char('b') => Some(Input::Brake(false)),
+            _ => None,
+        },
 
         _ => None,
     }
 }
 
-pub fn get_input() -> eyre::Result<Option<Input>> {
-    if !event::poll(Duration::ZERO)? {
+/// Waits for input at most `timeout`
+pub fn wait_input(timeout: Duration) -> eyre::Result<Option<Input>> {
+    if !event::poll(timeout)? {
         return Ok(None);
     }
 
     Ok(handle_event(&event::read()?))
 }
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyEvent, KeyEventState};
+
+    use super::*;
+
+    fn key(code: KeyCode, kind: KeyEventKind) -> Event {
+        Event::Key(KeyEvent {
+            code,
+            modifiers: KeyModifiers::NONE,
+            kind,
+            state: KeyEventState::NONE,
+        })
+    }
+
+    #[test]
+    fn speed_keys() {
+        let space = |kind| handle_event(&key(KeyCode::Char(' '), kind));
+        assert!(matches!(
+            space(KeyEventKind::Press),
+            Some(Input::Boost(true))
+        ));
+        assert!(matches!(
+            space(KeyEventKind::Repeat),
+            Some(Input::Boost(true))
+        ));
+        assert!(matches!(
+            space(KeyEventKind::Release),
+            Some(Input::Boost(false))
+        ));
+
+        let b = |kind| handle_event(&key(KeyCode::Char('b'), kind));
+        assert!(matches!(b(KeyEventKind::Press), Some(Input::Brake(true))));
+        assert!(matches!(b(KeyEventKind::Repeat), Some(Input::Brake(true))));
+        assert!(matches!(
+            b(KeyEventKind::Release),
+            Some(Input::Brake(false))
+        ));
+
+        // only the speed keys care about repeats and releases
+        let up = |kind| handle_event(&key(KeyCode::Up, kind));
+        assert!(matches!(
+            up(KeyEventKind::Press),
+            Some(Input::Move(Dir::Up))
+        ));
+        assert!(up(KeyEventKind::Repeat).is_none());
+        assert!(up(KeyEventKind::Release).is_none());
+    }
+}
@@ -0,0 +1,182 @@
+//! Engine of the snake game. The game itself doesn't need a terminal,
+//! see [`env`](mod@env) for running it from code, e.g. to train agents.
+
+use std::{fs::File, io::BufWriter, time::Duration};
+
+use clap::Args;
+use clap::Parser;
+use eyre::Context;
+
+mod ai;
+mod args;
+mod asciicast;
+mod bench;
+mod bot;
+mod campaign;
+mod code;
+mod daily;
+pub mod env;
+mod export;
+pub mod game;
+mod game_loop;
+mod grid;
+mod highscores;
+mod input;
+mod practice;
+mod render;
+mod replay;
+mod stats;
+mod storage;
+mod themes;
+
+pub use ai::Strategy;
+
+/// Runs the game with the command line arguments
+pub fn run() -> eyre::Result<()> {
+    let mut args = args::SnekGameCli::parse();
+
+    if let Some(shell) = args.complete {
+        let name = env!("CARGO_PKG_NAME");
+        let mut cli = args::SnekGameCli::augment_args(clap::Command::new(name));
+        clap_complete::generate(shell, &mut cli, name, &mut std::io::stdout());
+
+        return Ok(());
+    }
+
+    if let Some(args::Command::Stats) = &args.command {
+        let stats = match stats::Stats::default_path() {
+            Some(path) => stats::Stats::load(&path)?,
+            None => stats::Stats::default(),
+        };
+        stats::print(&stats);
+        return Ok(());
+    }
+
+    let code = args
+        .code
+        .as_deref()
+        .map(code::GameCode::decode)
+        .transpose()?;
+    if let Some(code) = &code {
+        code.conf.validate()?;
+        args.use_code_themes(code);
+    }
+
+    let theme = args::into_theme(&args)?;
+    // config of a new game, that is not a replay or a daily challenge
+    let new_conf = || match code {
+        Some(code) => Ok(code.conf),
+        None => args::create_game_conf(&args, &theme),
+    };
+
+    let bot_timeout = Duration::from_millis(args.bot_timeout);
+
+    if let Some(args::Command::Bench(b)) = &args.command {
+        return bench::run_command(b, &new_conf()?, bot_timeout);
+    }
+
+    if args.campaign {
+        return campaign::run(args.level, &theme, &args.theme_names(), args.fps);
+    }
+
+    let asciicast = match &args.asciicast {
+        Some(path) => {
+            let file = File::create(path)
+                .wrap_err_with(|| format!("Unable to create asciicast file {}", path.display()))?;
+            Some(Box::new(BufWriter::new(file)) as Box<dyn std::io::Write>)
+        }
+        None => None,
+    };
+
+    let playback = args
+        .replay
+        .as_deref()
+        .map(replay::Replay::load)
+        .transpose()?;
+    if let Some(replay) = &playback {
+        replay.conf.validate()?;
+    }
+
+    if let Some(path) = &args.export {
+        let format = export::Format::from_path(path)?;
+        let frames = match &playback {
+            Some(replay) => export::replay_frames(replay, &theme, args.export_tick)?,
+            None => {
+                let game = game::GameState::new(new_conf()?);
+                vec![(export::draw_frame(&game, &theme), game.expected_frametime())]
+            }
+        };
+        let file = File::create(path)
+            .wrap_err_with(|| format!("Unable to create export file {}", path.display()))?;
+        return export::export(&frames, format, BufWriter::new(file));
+    }
+
+    let asciicast = match (asciicast, &playback) {
+        (Some(out), Some(replay)) => return asciicast::export_replay(replay, &theme, out),
+        (asciicast, _) => asciicast,
+    };
+
+    let daily = args.daily.then(daily::Date::today);
+
+    let conf = match (&playback, daily) {
+        (Some(replay), _) => replay.conf.clone(),
+        (None, Some(date)) => daily::conf(date),
+        (None, None) => new_conf()?,
+    };
+    let game = game::GameState::new(conf);
+
+    let mut bot = args
+        .bot_cmd
+        .as_deref()
+        .map(|cmd| bot::Bot::spawn(cmd, bot_timeout))
+        .transpose()?;
+
+    let mut practice = args
+        .practice
+        .then(|| practice::Practice::new(args.rewind_ticks));
+
+    // games of bots and replays are not the player's games
+    let stats = (playback.is_none() && bot.is_none()).then(|| args.theme_names());
+
+    let options = game_loop::RunOptions {
+        playback: playback.as_ref(),
+        bot: bot.as_mut(),
+        practice: practice.as_mut(),
+        asciicast,
+        frame_rate: args.fps,
+        measure_latency: args.measure_latency,
+        // the size of replays, codes and daily challenges is part of the game
+        fit_field: args.fullscreen && playback.is_none() && daily.is_none() && args.code.is_none(),
+        speed_keys: game_loop::SpeedKeys {
+            boost: args.boost,
+            brake: args.brake,
+        },
+        stats,
+    };
+    let (game, replay) = game_loop::run(game, &theme, options)?;
+
+    if let Some(forfeit) = bot.as_ref().and_then(|bot| bot.forfeit()) {
+        println!("The bot forfeited: {forfeit}");
+    }
+
+    match (&playback, daily) {
+        (Some(_), _) => (),
+        // games of bots are not the player's scores
+        (None, _) if bot.is_some() => (),
+        (None, Some(date)) => daily::record_attempt(date, &game, &replay)?,
+        (None, None) => {
+            let rewound = practice.is_some_and(|p| p.rewinds() > 0);
+            highscores::record_game(&game, rewound);
+            println!(
+                "Game code: {}",
+                args.game_code(game.conf().clone()).encode()
+            );
+        }
+    }
+
+    if let Some(path) = &args.save_replay {
+        replay.save(path)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,287 @@
+//! Practice mode: the game can be rewound a few ticks back, e.g. after dying
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::game::{Coords, GameState, Owner};
+
+/// Number of rewinds in a row that can be made, going further back each time
+const MAX_REWINDS: usize = 5;
+
+/// Cells the head of a snake went through, the newest at the back. The body follows the
+/// head, so every recorded body of the snake is the last few cells of the trail when it
+/// was recorded, and a step adds only one cell instead of a copy of the whole snake.
+#[derive(Default)]
+struct Trail {
+    cells: VecDeque<Coords>,
+    /// Index of the first cell, the older cells are forgotten
+    start: usize,
+}
+
+/// Where a recorded body is in its [`Trail`]: the `len` cells before the index `end`,
+/// the head last
+#[derive(Clone, Copy)]
+struct Body {
+    end: usize,
+    len: usize,
+}
+
+impl Trail {
+    /// Adds the body, from the head to the tail. A body that doesn't `continue` the trail,
+    /// e.g. the snake placed anew after losing a life, is added whole.
+    fn record(
+        &mut self,
+        body: impl DoubleEndedIterator<Item = Coords> + Clone,
+        continues: bool,
+    ) -> Body {
+        let mut cells = body.clone();
+        let (head, neck) = (cells.next(), cells.next());
+        match self.cells.back() {
+            Some(&last) if continues && head == Some(last) => (),
+            Some(&last) if continues && neck == Some(last) => self.cells.extend(head),
+            _ => self.cells.extend(body.clone().rev()),
+        }
+        Body {
+            end: self.start + self.cells.len(),
+            len: body.count(),
+        }
+    }
+
+    /// Forgets the cells before the body
+    fn forget_before(&mut self, body: Body) {
+        let start = body.end - body.len;
+        self.cells.drain(..start - self.start);
+        self.start = start;
+    }
+
+    /// Cells of the body, from the head to the tail. The newer cells are forgotten.
+    fn rewind(&mut self, body: Body) -> VecDeque<Coords> {
+        let end = body.end - self.start;
+        let cells = self
+            .cells
+            .range(end - body.len..end)
+            .rev()
+            .copied()
+            .collect();
+        self.cells.truncate(end);
+        cells
+    }
+}
+
+/// State of the game before a step, kept without the bodies of the snakes
+struct Snapshot {
+    state: GameState,
+    snake: Body,
+    /// In the order of the rivals of the state
+    rivals: Vec<Body>,
+}
+
+pub struct Practice {
+    /// States of the game before the last steps, the newest at the back
+    history: VecDeque<Snapshot>,
+    trail: Trail,
+    rival_trails: HashMap<Owner, Trail>,
+    rewind_ticks: usize,
+    rewinds: u32,
+}
+
+impl Practice {
+    /// Every rewind goes `rewind_ticks` ticks back
+    pub fn new(rewind_ticks: usize) -> Self {
+        let rewind_ticks = rewind_ticks.max(1);
+        Self {
+            history: VecDeque::with_capacity(rewind_ticks * MAX_REWINDS),
+            trail: Trail::default(),
+            rival_trails: HashMap::new(),
+            rewind_ticks,
+            rewinds: 0,
+        }
+    }
+
+    /// Remembers the state of the game before a step
+    pub fn record(&mut self, game: &GameState) {
+        if self.history.len() == self.rewind_ticks * MAX_REWINDS {
+            self.history.pop_front();
+            // the snakes only move forwards, so the oldest ones start the earliest
+            if let Some(oldest) = self.history.front() {
+                self.trail.forget_before(oldest.snake);
+                // the rivals don't come back after dying, so their trails are forgotten
+                let mut alive = HashMap::with_capacity(oldest.rivals.len());
+                for (rival, &body) in oldest.state.rivals().iter().zip(&oldest.rivals) {
+                    if let Some(mut trail) = self.rival_trails.remove(&rival.owner) {
+                        trail.forget_before(body);
+                        alive.insert(rival.owner, trail);
+                    }
+                }
+                self.rival_trails = alive;
+            }
+        }
+
+        // a snake placed anew after losing a life doesn't continue the trail
+        let same_life = self
+            .history
+            .back()
+            .is_some_and(|last| last.state.lives_left() == game.lives_left());
+        let snake = self.trail.record(game.snake_iter(), same_life);
+        let rivals = game
+            .rivals()
+            .iter()
+            .map(|r| {
+                let trail = self.rival_trails.entry(r.owner).or_default();
+                trail.record(r.body.iter().copied(), true)
+            })
+            .collect();
+
+        self.history.push_back(Snapshot {
+            state: game.clone_without_snakes(),
+            snake,
+            rivals,
+        });
+    }
+
+    /// State of the game `rewind_ticks` ticks back, or as far back as remembered.
+    /// The newer states are forgotten.
+    pub fn rewind(&mut self) -> Option<GameState> {
+        let keep = self.history.len().saturating_sub(self.rewind_ticks);
+        let Snapshot {
+            mut state,
+            snake,
+            rivals,
+        } = self.history.drain(keep..).next()?;
+
+        state.set_snake(self.trail.rewind(snake));
+        let bodies: Vec<VecDeque<Coords>> = state
+            .rivals()
+            .iter()
+            .zip(rivals)
+            .map(|(r, body)| self.rival_trails.get_mut(&r.owner).unwrap().rewind(body))
+            .collect();
+        state.set_rival_bodies(bodies);
+        self.rewinds += 1;
+        Some(state)
+    }
+
+    /// Number of rewinds made, games with rewinds are not ranked
+    pub fn rewinds(&self) -> u32 {
+        self.rewinds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::{
+        ai::{self, Strategy},
+        game::{Dir, GameConf, GameStatus, SelfHit},
+    };
+
+    #[test]
+    fn rewinds_to_the_same_state() {
+        let mut game = GameState::new(crate::game::test_conf());
+        let mut practice = Practice::new(3);
+        for _ in 0..10 {
+            practice.record(&game);
+            game.make_step(None);
+        }
+        let ahead = game.clone();
+
+        let mut game = practice.rewind().unwrap();
+        assert_eq!(game.tick(), 7);
+        for _ in 0..3 {
+            game.make_step(None);
+        }
+        assert_eq!(
+            game.snake_iter().collect::<Vec<_>>(),
+            ahead.snake_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(game.food()[0].pos, ahead.food()[0].pos);
+
+        assert_eq!(practice.rewind().unwrap().tick(), 4);
+        assert_eq!(practice.rewind().unwrap().tick(), 1);
+        assert_eq!(practice.rewind().unwrap().tick(), 0);
+        assert!(practice.rewind().is_none());
+        assert_eq!(practice.rewinds(), 4);
+    }
+
+    #[test]
+    fn rewinds_restore_the_snake() {
+        let conf = GameConf {
+            lives: 5,
+            portals: 2,
+            on_self_hit: SelfHit::Cut,
+            ..crate::game::test_conf()
+        };
+        let mut game = GameState::new(conf);
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut practice = Practice::new(4);
+        // full copies of the states before every step
+        let mut states: Vec<GameState> = Vec::new();
+        let mut respawns = 0;
+
+        for i in 1..1000 {
+            if i % 30 == 0 || game.status() == GameStatus::Dead {
+                game = practice.rewind().unwrap();
+                let tick = game.tick() as usize;
+                assert_eq!(
+                    game.snake_iter().collect::<Vec<_>>(),
+                    states[tick].snake_iter().collect::<Vec<_>>(),
+                    "tick {tick}"
+                );
+                states.truncate(tick);
+                continue;
+            }
+
+            // the snake sometimes turns at random, so it loses lives
+            let turn = match rng.gen_ratio(1, 8) {
+                true => Some([Dir::Up, Dir::Down, Dir::Left, Dir::Right][rng.gen_range(0..4)]),
+                false => ai::player_turn(&game, Strategy::Greedy, &mut rng),
+            };
+            practice.record(&game);
+            states.push(game.clone());
+            game.make_step(turn.filter(|_| game.status() == GameStatus::Ongoing));
+            respawns += usize::from(game.lives_left() < states[states.len() - 1].lives_left());
+        }
+        assert!(respawns > 0);
+    }
+
+    #[test]
+    fn rewinds_restore_the_rivals() {
+        let conf = GameConf {
+            lives: 5,
+            rivals: 3,
+            moving_food: 1,
+            ..crate::game::test_conf()
+        };
+        let mut game = GameState::new(conf);
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut practice = Practice::new(4);
+        let mut states: Vec<GameState> = Vec::new();
+        let bodies = |game: &GameState| -> Vec<(Owner, Vec<Coords>)> {
+            let rivals = game.rivals().iter();
+            rivals
+                .map(|r| (r.owner, r.body.iter().copied().collect()))
+                .collect()
+        };
+        let mut rivals_rewound = 0;
+
+        for i in 1..500 {
+            if i % 25 == 0 || game.status() == GameStatus::Dead {
+                game = practice.rewind().unwrap();
+                let tick = game.tick() as usize;
+                assert_eq!(bodies(&game), bodies(&states[tick]), "tick {tick}");
+                let food = |game: &GameState| game.food().iter().map(|f| f.pos).collect::<Vec<_>>();
+                assert_eq!(food(&game), food(&states[tick]), "tick {tick}");
+                rivals_rewound += game.rivals().len();
+                states.truncate(tick);
+                continue;
+            }
+
+            let turn = ai::player_turn(&game, Strategy::Greedy, &mut rng);
+            practice.record(&game);
+            states.push(game.clone());
+            game.make_step(turn.filter(|_| game.status() == GameStatus::Ongoing));
+        }
+        assert!(rivals_rewound > 0);
+    }
+}
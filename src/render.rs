@@ -1,23 +1,35 @@
 use std::{
     fs::File,
-    io::{StdoutLock, Write},
+    io::{IsTerminal, StdoutLock, Write},
     mem::ManuallyDrop,
     os::fd::{AsRawFd, FromRawFd},
+    time::Duration,
 };
 
-use crossterm::{cursor, terminal, ExecutableCommand, QueueableCommand};
+use crossterm::{cursor, event, style::Color, terminal, ExecutableCommand, QueueableCommand};
 use ndarray::Array2;
+use unicode_width::UnicodeWidthStr;
 
 use crate::{
-    game::{Dir, GameState},
-    themes::{BorderTheme, FullTheme},
+    asciicast::Asciicast,
+    game::{Coords, Dir, GameState, GameStatus, Goal, Owner, SelfHit, PLAYER},
+    themes::{BorderTheme, FullTheme, SnakeTheme},
 };
 
 #[derive(Clone, Copy)]
 enum FieldCell {
     Empty,
-    Food(usize), // stores the id of the food
+    Food(usize),   // stores the id of the food
+    Portal(usize), // stores the index of the portal pair
+    Obstacle,
+    FoodTrail,
+    Snake(SnakePart, Owner),
+    /// Where the snake died
+    Fatal,
+}
 
+#[derive(Clone, Copy)]
+enum SnakePart {
     HeadUp,
     HeadDown,
     HeadLeft,
@@ -37,157 +49,384 @@ enum FieldCell {
     BodyDownLeft,
 }
 
-impl FieldCell {
+impl SnakePart {
     // Head that looks to `dir`
     fn head_from(dir: Dir) -> Self {
         match dir {
-            Dir::Up => FieldCell::HeadUp,
-            Dir::Down => FieldCell::HeadDown,
-            Dir::Left => FieldCell::HeadLeft,
-            Dir::Right => FieldCell::HeadRight,
+            Dir::Up => SnakePart::HeadUp,
+            Dir::Down => SnakePart::HeadDown,
+            Dir::Left => SnakePart::HeadLeft,
+            Dir::Right => SnakePart::HeadRight,
         }
     }
 
     // Tail that looks to `dir`
     fn tail_from(dir: Dir) -> Self {
         match dir {
-            Dir::Up => FieldCell::TailUp,
-            Dir::Down => FieldCell::TailDown,
-            Dir::Left => FieldCell::TailLeft,
-            Dir::Right => FieldCell::TailRight,
+            Dir::Up => SnakePart::TailUp,
+            Dir::Down => SnakePart::TailDown,
+            Dir::Left => SnakePart::TailLeft,
+            Dir::Right => SnakePart::TailRight,
         }
     }
 
     // Body which has another body part to `dir1` and `dir2` from itself
     fn body_from(dir1: Dir, dir2: Dir) -> Self {
         match (dir1, dir2) {
-            (Dir::Down, Dir::Up) | (Dir::Up, Dir::Down) => FieldCell::BodyVertical,
-            (Dir::Right, Dir::Left) | (Dir::Left, Dir::Right) => FieldCell::BodyHorizontal,
-            (Dir::Up, Dir::Left) | (Dir::Left, Dir::Up) => FieldCell::BodyUpLeft,
-            (Dir::Down, Dir::Left) | (Dir::Left, Dir::Down) => FieldCell::BodyDownLeft,
-            (Dir::Up, Dir::Right) | (Dir::Right, Dir::Up) => FieldCell::BodyUpRight,
-            (Dir::Down, Dir::Right) | (Dir::Right, Dir::Down) => FieldCell::BodyDownRight,
-            _ => FieldCell::BodyHorizontal,
+            (Dir::Down, Dir::Up) | (Dir::Up, Dir::Down) => SnakePart::BodyVertical,
+            (Dir::Right, Dir::Left) | (Dir::Left, Dir::Right) => SnakePart::BodyHorizontal,
+            (Dir::Up, Dir::Left) | (Dir::Left, Dir::Up) => SnakePart::BodyUpLeft,
+            (Dir::Down, Dir::Left) | (Dir::Left, Dir::Down) => SnakePart::BodyDownLeft,
+            (Dir::Up, Dir::Right) | (Dir::Right, Dir::Up) => SnakePart::BodyUpRight,
+            (Dir::Down, Dir::Right) | (Dir::Right, Dir::Down) => SnakePart::BodyDownRight,
+            _ => SnakePart::BodyHorizontal,
         }
     }
 
-    fn draw_with_theme(
-        &self,
-        f: &mut impl Write,
-        t: &FullTheme,
-        color: bool,
-    ) -> Result<(), std::io::Error> {
+    /// Glyph of the part of the snake of `owner`, rivals only have a head and a body
+    fn glyph<'t>(&self, t: &'t SnakeTheme, owner: Owner) -> &'t str {
+        let head = matches!(
+            self,
+            SnakePart::HeadUp | SnakePart::HeadDown | SnakePart::HeadLeft | SnakePart::HeadRight
+        );
+        match self {
+            _ if owner != PLAYER && head => &t.rival_head,
+            _ if owner != PLAYER => &t.rival_body,
+            SnakePart::HeadUp => &t.head_up,
+            SnakePart::HeadDown => &t.head_down,
+            SnakePart::HeadLeft => &t.head_left,
+            SnakePart::HeadRight => &t.head_right,
+            SnakePart::BodyVertical => &t.body_vertical,
+            SnakePart::BodyHorizontal => &t.body_horizontal,
+            SnakePart::BodyUpRight => &t.body_up_right,
+            SnakePart::BodyDownRight => &t.body_down_right,
+            SnakePart::BodyUpLeft => &t.body_up_left,
+            SnakePart::BodyDownLeft => &t.body_down_left,
+            SnakePart::TailUp => &t.tail_up,
+            SnakePart::TailDown => &t.tail_down,
+            SnakePart::TailLeft => &t.tail_left,
+            SnakePart::TailRight => &t.tail_right,
+        }
+    }
+}
+
+impl FieldCell {
+    /// Glyph of the cell in theme `t`, with color if it has one
+    fn glyph<'t>(&self, t: &'t FullTheme) -> (&'t str, Option<Color>) {
         let str: &str = match self {
-            FieldCell::Food(id) => {
-                t.food.display_with_id(f, *id, color)?;
-                return Ok(());
+            FieldCell::Food(id) => return t.food.glyph_with_id(*id),
+            FieldCell::Portal(i) => return t.board.portal_with_index(*i),
+            FieldCell::Snake(part, owner) => {
+                return (part.glyph(&t.snake, *owner), t.snake.color(*owner))
             }
             FieldCell::Empty => &t.board.empty,
-            FieldCell::HeadUp => &t.snake.head_up,
-            FieldCell::HeadDown => &t.snake.head_down,
-            FieldCell::HeadLeft => &t.snake.head_left,
-            FieldCell::HeadRight => &t.snake.head_right,
-            FieldCell::BodyVertical => &t.snake.body_vertical,
-            FieldCell::BodyHorizontal => &t.snake.body_horizontal,
-            FieldCell::BodyUpRight => &t.snake.body_up_right,
-            FieldCell::BodyDownRight => &t.snake.body_down_right,
-            FieldCell::BodyUpLeft => &t.snake.body_up_left,
-            FieldCell::BodyDownLeft => &t.snake.body_down_left,
-            FieldCell::TailUp => &t.snake.tail_up,
-            FieldCell::TailDown => &t.snake.tail_down,
-            FieldCell::TailLeft => &t.snake.tail_left,
-            FieldCell::TailRight => &t.snake.tail_right,
+            FieldCell::Obstacle => &t.board.obstacle,
+            FieldCell::FoodTrail => &t.board.food_trail,
+            FieldCell::Fatal => return (&t.board.fatal, t.board.fatal_color),
         };
-        write!(f, "{}", str)
+        (str, None)
     }
 }
 
-/// This struct holds `StdoutLock` which means, that while it exists,
-/// no other thread can write into stdout.
-/// Which is fine since the program is single threaded
-pub struct Renderer {
-    screen: Array2<FieldCell>,
-    stdout_lock: StdoutLock<'static>,
-    /// By default [`std::io::Stdout`] flushes on `\n` which we don't want
-    /// see: <https://github.com/rust-lang/libs-team/issues/148>
-    /// and: <https://github.com/rust-lang/rust/pull/78515>
-    out_buf: Vec<u8>,
-    color: bool,
+/// Fills `screen` with the cells of the game
+fn setup_screen(screen: &mut Array2<FieldCell>, game: &GameState) {
+    screen.fill(FieldCell::Empty);
+
+    for c in &game.conf().obstacles {
+        screen[[c.y, c.x]] = FieldCell::Obstacle;
+    }
+
+    for (i, &(a, b)) in game.portals().iter().enumerate() {
+        screen[[a.y, a.x]] = FieldCell::Portal(i);
+        screen[[b.y, b.x]] = FieldCell::Portal(i);
+    }
+
+    for rival in game.rivals() {
+        draw_snake(
+            screen,
+            game,
+            rival.body.iter().copied(),
+            rival.dir,
+            rival.owner,
+        );
+    }
+
+    // the respawned snake blinks until it starts moving
+    let hidden =
+        matches!(game.status(), GameStatus::Respawning { ticks_left } if ticks_left % 2 == 1);
+    if !hidden {
+        // the player is on top of the rivals, if they share a cell after a collision
+        draw_snake(screen, game, game.snake_iter(), game.snake_dir(), PLAYER);
+    }
+
+    for food in game.food() {
+        screen[[food.pos.y, food.pos.x]] = FieldCell::Food(food.id);
+    }
+
+    // shows which way the food went, so moving food can be told apart
+    for from in game.food_trails() {
+        if let FieldCell::Empty = screen[[from.y, from.x]] {
+            screen[[from.y, from.x]] = FieldCell::FoodTrail;
+        }
+    }
+
+    if let (GameStatus::Dead, Some(death)) = (game.status(), game.last_death()) {
+        let c = death.cause.pos();
+        screen[[c.y, c.x]] = FieldCell::Fatal;
+    }
 }
 
-impl Renderer {
-    pub fn init(game_width: usize, game_height: usize) -> Result<Self, std::io::Error> {
-        terminal::enable_raw_mode()?;
+/// Fills the cells of the snake with `body` going from the head to the tail
+fn draw_snake(
+    screen: &mut Array2<FieldCell>,
+    game: &GameState,
+    mut body: impl Iterator<Item = Coords>,
+    snake_dir: Dir,
+    owner: Owner,
+) {
+    // direction in which the snake moved from `from` to `to`,
+    // it can only be found forwards because of the portals
+    let dir = |from: Coords, to: Coords| game.dir_to(from, to).unwrap_or(snake_dir);
+    let mut put = |c: Coords, part| screen[[c.y, c.x]] = FieldCell::Snake(part, owner);
 
-        let mut this = Self {
-            screen: Array2::from_elem([game_height, game_width], FieldCell::Empty),
-            stdout_lock: std::io::stdout().lock(),
-            out_buf: Vec::with_capacity(8 * 1024),
-            color: !crossterm::style::Colored::ansi_color_disabled(),
-        };
+    let Some(head) = body.next() else {
+        return;
+    };
 
-        this.out_buf.queue(cursor::Hide)?;
-        this.out_buf
-            .queue(terminal::Clear(terminal::ClearType::All))?;
-        this.flush_buf()?;
+    let mut prev = head;
+    let mut curr = body.next();
+    let mut next = body.next();
 
-        Ok(this)
+    while let (Some(some_curr), Some(some_next)) = (curr, next) {
+        let dir1 = dir(some_curr, prev).opposite();
+        let dir2 = dir(some_next, some_curr);
+        put(some_curr, SnakePart::body_from(dir1, dir2));
+
+        (prev, curr, next) = (some_curr, next, body.next());
     }
 
-    /// Queues clear escape sequence, so the next frame will clear the terminal
-    pub fn queue_clear(&mut self) {
-        self.out_buf
-            .queue(terminal::Clear(terminal::ClearType::All))
-            .expect("writing into Vec cannot fail");
+    if let Some(tail) = curr {
+        put(tail, SnakePart::tail_from(dir(tail, prev).opposite()));
     }
 
-    pub fn render_game(
-        &mut self,
-        game: &GameState,
-        theme: &FullTheme,
-    ) -> Result<(), std::io::Error> {
-        // === Setup the screen
+    // the head goes last to be on top, when the snake passes through itself
+    put(head, SnakePart::head_from(snake_dir));
+}
 
-        self.screen.fill(FieldCell::Empty);
+/// Every cell of the field takes this many columns of the terminal
+pub const CELL_WIDTH: usize = 2;
 
-        let w = game.width();
-        let h = game.height();
-        let mut snake = game.snake_iter();
-        let head = snake.next().unwrap();
+/// Describes how much space around the field is taken by the border and the HUD
+/// for a given theme, so the field can be fitted into the terminal window
+#[derive(Clone, Copy, Debug)]
+pub struct Layout {
+    border_left: usize,
+    border_right: usize,
+    border_top: usize,
+    border_bottom: usize,
+    hud_rows: usize,
+}
+
+impl Layout {
+    pub fn new(theme: &FullTheme) -> Self {
+        let (border_left, border_right, border_top, border_bottom) = match &theme.board.border {
+            Some(b) => (
+                [&b.top_left, &b.vertical, &b.bottom_left]
+                    .iter()
+                    .map(|s| s.width())
+                    .max()
+                    .unwrap_or(0),
+                [&b.top_right, &b.vertical, &b.bottom_right]
+                    .iter()
+                    .map(|s| s.width())
+                    .max()
+                    .unwrap_or(0),
+                1,
+                1,
+            ),
+            None => (0, 0, 0, 0),
+        };
+
+        // with a border the score is drawn on top of it
+        let hud_rows = match theme.board.border {
+            None if theme.display_score => 1,
+            _ => 0,
+        };
+
+        Self {
+            border_left,
+            border_right,
+            border_top,
+            border_bottom,
+            hud_rows,
+        }
+    }
 
-        self.screen[[head.y, head.x]] = FieldCell::head_from(game.snake_dir());
+    /// Largest field that fits into `columns` by `rows` terminal.
+    /// Returns zero sized field if nothing fits.
+    pub fn max_field_size(&self, columns: usize, rows: usize) -> (usize, usize) {
+        let w = columns.saturating_sub(self.border_left + self.border_right) / CELL_WIDTH;
+        let h = rows.saturating_sub(self.border_top + self.border_bottom + self.hud_rows);
+        (w, h)
+    }
+
+    /// Size of the terminal needed to draw `w` by `h` field
+    pub fn screen_size(&self, w: usize, h: usize) -> (usize, usize) {
+        (
+            self.border_left + w * CELL_WIDTH + self.border_right,
+            self.hud_rows + self.border_top + h + self.border_bottom,
+        )
+    }
+}
+
+/// Where the [`Renderer`] draws. Works like a terminal: the cursor is moved,
+/// and printed text advances it.
+pub trait Backend {
+    fn move_to(&mut self, x: u16, y: u16) -> std::io::Result<()>;
+    fn print(&mut self, s: &str, color: Option<Color>) -> std::io::Result<()>;
+    fn clear(&mut self) -> std::io::Result<()>;
+    /// Shows everything drawn since the last flush
+    fn flush(&mut self) -> std::io::Result<()>;
+    /// Called when the size of the screen changes
+    fn resize(&mut self, _columns: u16, _rows: u16) -> std::io::Result<()> {
+        Ok(())
+    }
+}
 
-        let mut prev = head;
-        let mut curr = snake.next();
-        let mut next = snake.next();
+/// Lays out the game on the screen of the [`Backend`]
+pub struct Renderer<B: Backend> {
+    screen: Array2<FieldCell>,
+    backend: B,
+    layout: Layout,
+    /// Top left corner of the drawn game on the screen,
+    /// or `None` if the screen is too small to fit the game
+    origin: Option<(u16, u16)>,
+    /// Size of the screen in columns and rows
+    size: (u16, u16),
+    /// Drawn over the middle of the field, see [`Renderer::set_hint`]
+    hint: Option<&'static str>,
+}
 
-        while let (Some(some_curr), Some(some_next)) = (curr, next) {
-            let dir1 = some_curr.compare(&prev, w, h);
-            let dir2 = some_curr.compare(&some_next, w, h);
-            self.screen[[some_curr.y, some_curr.x]] = FieldCell::body_from(dir1, dir2);
+impl Renderer<AnsiBackend> {
+    /// Creates renderer which draws into the terminal.
+    /// Everything drawn, from the first frame on, is recorded into `asciicast` if it's given.
+    pub fn init(
+        game_width: usize,
+        game_height: usize,
+        theme: &FullTheme,
+        asciicast: Option<Box<dyn Write>>,
+    ) -> Result<Self, std::io::Error> {
+        let mut this = Self::new(AnsiBackend::init()?, game_width, game_height, theme);
 
-            (prev, curr, next) = (some_curr, next, snake.next());
+        let (columns, rows) = terminal::size()?;
+        if let Some(out) = asciicast {
+            this.backend.record(Asciicast::new(out, columns, rows)?);
         }
+        // the size is already in the header of the recording
+        this.size = (columns, rows);
+        this.place()?;
+        this.backend.flush()?;
+
+        Ok(this)
+    }
+}
 
-        if let Some(tail) = curr {
-            self.screen[[tail.y, tail.x]] = FieldCell::tail_from(tail.compare(&prev, w, h));
+impl<B: Backend> Renderer<B> {
+    /// Creates renderer, which draws in the top left corner of the `backend`
+    pub fn new(backend: B, game_width: usize, game_height: usize, theme: &FullTheme) -> Self {
+        let layout = Layout::new(theme);
+        let (columns, rows) = layout.screen_size(game_width, game_height);
+        Self {
+            screen: Array2::from_elem([game_height, game_width], FieldCell::Empty),
+            backend,
+            layout,
+            origin: Some((0, 0)),
+            size: (columns as u16, rows as u16),
+            hint: None,
         }
+    }
+
+    pub fn backend_mut(&mut self) -> &mut B {
+        &mut self.backend
+    }
+
+    pub fn into_backend(self) -> B {
+        self.backend
+    }
+
+    /// Size of the screen needed to draw the game
+    pub fn screen_size(&self) -> (u16, u16) {
+        let (h, w) = self.screen.dim();
+        let (columns, rows) = self.layout.screen_size(w, h);
+        (columns as u16, rows as u16)
+    }
 
-        for food in game.food() {
-            self.screen[[food.pos.y, food.pos.x]] = FieldCell::Food(food.id);
+    /// Recomputes where the game is drawn for the new screen size
+    /// and clears the screen
+    pub fn resize(&mut self, columns: u16, rows: u16) -> std::io::Result<()> {
+        self.backend.resize(columns, rows)?;
+        self.size = (columns, rows);
+        self.place()
+    }
+
+    /// Shows the text over the middle of the field in the next frames, e.g. what the player
+    /// can do while the game waits. It's not drawn if the field is too narrow for it.
+    pub fn set_hint(&mut self, hint: Option<&'static str>) {
+        self.hint = hint;
+    }
+
+    /// Centers the game on the screen and clears it
+    fn place(&mut self) -> std::io::Result<()> {
+        let (columns, rows) = self.size;
+        let (needed_columns, needed_rows) = self.screen_size();
+
+        self.origin = if needed_columns <= columns && needed_rows <= rows {
+            // center the game on the screen
+            let x = (columns - needed_columns) / 2;
+            let y = (rows - needed_rows) / 2;
+            Some((x, y))
+        } else {
+            None
+        };
+
+        self.backend.clear()
+    }
+
+    pub fn render_game(
+        &mut self,
+        game: &GameState,
+        theme: &FullTheme,
+    ) -> Result<(), std::io::Error> {
+        // the field was resized to fit the screen
+        if self.screen.dim() != (game.height(), game.width()) {
+            self.screen = Array2::from_elem([game.height(), game.width()], FieldCell::Empty);
+            self.place()?;
         }
 
-        // === Start writng
+        let Some(origin) = self.origin else {
+            self.backend.move_to(0, 0)?;
+            self.backend.print("Terminal is too small", None)?;
+            return self.backend.flush();
+        };
 
-        self.out_buf.queue(cursor::MoveTo(0, 0))?;
+        setup_screen(&mut self.screen, game);
 
         if let Some(border) = &theme.board.border {
-            self.render_screen_with_border(game, theme, border)?;
+            self.render_screen_with_border(game, theme, border, origin)?;
         } else {
-            self.render_screen_with_no_border(game, theme)?;
+            self.render_screen_with_no_border(game, theme, origin)?;
         }
 
-        self.flush_buf()?;
+        let space = game.width() * CELL_WIDTH;
+        if let Some(hint) = self.hint.filter(|hint| hint.width() <= space) {
+            let (x, y) = origin;
+            let x = x as usize + self.layout.border_left + (space - hint.width()) / 2;
+            let y = y as usize + self.layout.hud_rows + self.layout.border_top + game.height() / 2;
+            self.backend.move_to(x as u16, y as u16)?;
+            self.backend.print(hint, None)?;
+        }
+
+        self.backend.flush()?;
 
         Ok(())
     }
@@ -197,30 +436,38 @@ impl Renderer {
         game: &GameState,
         theme: &FullTheme,
         border: &BorderTheme,
+        (x, mut y): (u16, u16),
     ) -> Result<(), std::io::Error> {
-        let out_buf = &mut self.out_buf;
-        write!(out_buf, "{}", border.top_left)?;
+        let out = &mut self.backend;
+        out.move_to(x, y)?;
+        out.print(&border.top_left, None)?;
         for _ in 0..game.width() {
-            write!(out_buf, "{}", border.horizontal)?;
+            out.print(&border.horizontal, None)?;
         }
-        write!(out_buf, "{}", border.top_right)?;
+        out.print(&border.top_right, None)?;
         if theme.display_score {
-            out_buf.queue(cursor::MoveToColumn(2))?;
-            write!(out_buf, "Score: {}", game.score())?;
+            // keep the score on top of the horizontal part of the border
+            let space = (game.width() * CELL_WIDTH).saturating_sub(2);
+            out.move_to(x + self.layout.border_left as u16 + 1, y)?;
+            out.print(&hud_text(game, space), None)?;
         }
-        write!(out_buf, "\r\n")?;
         for row in self.screen.outer_iter() {
-            write!(out_buf, "{}", border.vertical)?;
+            y += 1;
+            out.move_to(x, y)?;
+            out.print(&border.vertical, None)?;
             for cell in &row {
-                cell.draw_with_theme(out_buf, theme, self.color)?;
+                let (glyph, color) = cell.glyph(theme);
+                out.print(glyph, color)?;
             }
-            write!(out_buf, "{}\r\n", border.vertical)?;
+            out.print(&border.vertical, None)?;
         }
-        write!(out_buf, "{}", border.bottom_left)?;
+        y += 1;
+        out.move_to(x, y)?;
+        out.print(&border.bottom_left, None)?;
         for _ in 0..game.width() {
-            write!(out_buf, "{}", border.horizontal)?;
+            out.print(&border.horizontal, None)?;
         }
-        write!(out_buf, "{}", border.bottom_right)?;
+        out.print(&border.bottom_right, None)?;
         Ok(())
     }
 
@@ -228,41 +475,776 @@ impl Renderer {
         &mut self,
         game: &GameState,
         theme: &FullTheme,
+        (x, mut y): (u16, u16),
     ) -> Result<(), std::io::Error> {
-        let out_buf = &mut self.out_buf;
+        let out = &mut self.backend;
         if theme.display_score {
-            write!(out_buf, "Score: {}\r\n", game.score())?;
+            out.move_to(x, y)?;
+            let space = game.width() * CELL_WIDTH;
+            out.print(&hud_text(game, space), None)?;
+            y += 1;
         }
-        for (i, row) in self.screen.outer_iter().enumerate() {
-            if i != 0 {
-                write!(out_buf, "\r\n")?;
-            }
+        for row in self.screen.outer_iter() {
+            out.move_to(x, y)?;
             for cell in &row {
-                cell.draw_with_theme(out_buf, theme, self.color)?;
+                let (glyph, color) = cell.glyph(theme);
+                out.print(glyph, color)?;
+            }
+            y += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Backend that writes ANSI escape sequences into the terminal.
+///
+/// This struct holds `StdoutLock` which means, that while it exists,
+/// no other thread can write into stdout.
+/// Which is fine since the program is single threaded
+pub struct AnsiBackend {
+    /// `None` if the backend doesn't draw to the terminal
+    stdout_lock: Option<StdoutLock<'static>>,
+    /// By default [`std::io::Stdout`] flushes on `\n` which we don't want
+    /// see: <https://github.com/rust-lang/libs-team/issues/148>
+    /// and: <https://github.com/rust-lang/rust/pull/78515>
+    out_buf: Vec<u8>,
+    color: bool,
+    recording: Option<Asciicast>,
+    /// The terminal reports key releases, see [`AnsiBackend::reports_key_release`]
+    key_release: bool,
+}
+
+impl AnsiBackend {
+    /// Takes over the terminal, until the backend is dropped
+    pub fn init() -> std::io::Result<Self> {
+        terminal::enable_raw_mode()?;
+
+        let mut this = Self::headless();
+        let mut stdout_lock = std::io::stdout().lock();
+        // without the enhancement the terminal only reports key presses
+        if terminal::supports_keyboard_enhancement().unwrap_or(false) {
+            stdout_lock.execute(event::PushKeyboardEnhancementFlags(
+                event::KeyboardEnhancementFlags::REPORT_EVENT_TYPES,
+            ))?;
+            this.key_release = true;
+        }
+        this.stdout_lock = Some(stdout_lock);
+        Ok(this)
+    }
+
+    /// True if the terminal reports when keys are released, so they can be held
+    pub fn reports_key_release(&self) -> bool {
+        self.key_release
+    }
+
+    /// Creates backend which doesn't draw to the terminal,
+    /// the frames only go to the recording
+    pub fn headless() -> Self {
+        let mut out_buf = Vec::with_capacity(8 * 1024);
+        out_buf
+            .queue(cursor::Hide)
+            .and_then(|b| b.queue(terminal::Clear(terminal::ClearType::All)))
+            .expect("writing into Vec cannot fail");
+
+        Self {
+            stdout_lock: None,
+            out_buf,
+            color: !crossterm::style::Colored::ansi_color_disabled(),
+            recording: None,
+            key_release: false,
+        }
+    }
+
+    /// Starts recording everything that is drawn
+    pub fn record(&mut self, recording: Asciicast) {
+        self.recording = Some(recording);
+    }
+
+    /// Stops the recording and writes out everything recorded
+    pub fn finish_recording(&mut self) -> std::io::Result<()> {
+        match self.recording.take() {
+            Some(mut recording) => recording.finish(),
+            None => Ok(()),
+        }
+    }
+
+    /// Advances time of the recording if it uses virtual time
+    pub fn advance_recording(&mut self, dt: Duration) {
+        if let Some(recording) = &mut self.recording {
+            recording.advance(dt);
+        }
+    }
+}
+
+impl Backend for AnsiBackend {
+    fn move_to(&mut self, x: u16, y: u16) -> std::io::Result<()> {
+        self.out_buf.queue(cursor::MoveTo(x, y))?;
+        Ok(())
+    }
+
+    fn print(&mut self, s: &str, color: Option<Color>) -> std::io::Result<()> {
+        match color {
+            Some(color) if self.color => {
+                write!(
+                    self.out_buf,
+                    "{}",
+                    crossterm::style::Stylize::with(s, color)
+                )
             }
+            _ => write!(self.out_buf, "{s}"),
         }
+    }
+
+    fn clear(&mut self) -> std::io::Result<()> {
+        self.out_buf
+            .queue(terminal::Clear(terminal::ClearType::All))?;
         Ok(())
     }
 
-    fn flush_buf(&mut self) -> std::io::Result<()> {
-        // Use raw fd to bypass annoying line buffer in Stdout.
+    fn flush(&mut self) -> std::io::Result<()> {
+        if let Some(recording) = &mut self.recording {
+            recording.output(&self.out_buf)?;
+        }
+
+        if let Some(stdout_lock) = &self.stdout_lock {
+            // Use raw fd to bypass annoying line buffer in Stdout.
 
-        // SAFETY: Self holds the lock to stdout
-        unsafe {
-            let mut raw_stdout = ManuallyDrop::new(File::from_raw_fd(self.stdout_lock.as_raw_fd()));
-            raw_stdout.write_all(&self.out_buf)?;
+            // SAFETY: Self holds the lock to stdout
+            unsafe {
+                let mut raw_stdout = ManuallyDrop::new(File::from_raw_fd(stdout_lock.as_raw_fd()));
+                raw_stdout.write_all(&self.out_buf)?;
+            }
         }
 
         self.out_buf.clear();
 
         Ok(())
     }
+
+    fn resize(&mut self, columns: u16, rows: u16) -> std::io::Result<()> {
+        if let Some(recording) = &mut self.recording {
+            recording.resize(columns, rows)?;
+        }
+        Ok(())
+    }
 }
 
-impl Drop for Renderer {
+impl Drop for AnsiBackend {
     fn drop(&mut self) {
-        // ignore io errors
-        let _ = self.stdout_lock.execute(cursor::Show);
-        let _ = terminal::disable_raw_mode();
+        if let Some(stdout_lock) = &mut self.stdout_lock {
+            // ignore io errors
+            if self.key_release {
+                let _ = stdout_lock.execute(event::PopKeyboardEnhancementFlags);
+            }
+            let _ = stdout_lock.execute(cursor::Show);
+            let _ = terminal::disable_raw_mode();
+        }
+    }
+}
+
+/// Checks if the terminal draws emoji two columns wide,
+/// as some terminals and fonts draw them narrow.
+/// The terminal is asked only if `probe` is set, since terminals that don't answer
+/// stall the start until the query times out. Otherwise, or if the terminal can't tell,
+/// the width is taken from the unicode tables.
+pub fn emoji_is_wide(probe: bool) -> bool {
+    const EMOJI: &str = "🍎";
+    let probed = match probe && std::io::stdout().is_terminal() {
+        true => probe_width(EMOJI).ok(),
+        false => None,
+    };
+    probed.unwrap_or(EMOJI.width()) == CELL_WIDTH
+}
+
+/// Measures how many columns the terminal actually uses to draw `s`
+/// by writing it at the start of the current line and querying the cursor position.
+/// The line is cleared afterwards.
+fn probe_width(s: &str) -> std::io::Result<usize> {
+    terminal::enable_raw_mode()?;
+
+    let mut stdout = std::io::stdout().lock();
+    let result = (|| {
+        stdout.queue(cursor::MoveToColumn(0))?;
+        write!(stdout, "{s}")?;
+        stdout.flush()?;
+        let (width, _) = cursor::position()?;
+
+        stdout.queue(cursor::MoveToColumn(0))?;
+        stdout.queue(terminal::Clear(terminal::ClearType::CurrentLine))?;
+        stdout.flush()?;
+        Ok(width as usize)
+    })();
+
+    terminal::disable_raw_mode()?;
+    result
+}
+
+/// Text of the HUD that fits into `columns` columns of the terminal.
+/// On narrow fields the less important parts are dropped, and then the score is cut.
+fn hud_text(game: &GameState, columns: usize) -> String {
+    let mut parts = vec![format!("Score: {}", game.score())];
+    if let Some(secs) = game.respawn_countdown() {
+        parts.push(format!("Ready: {secs}"));
+    }
+    match game.conf().goal {
+        None => (),
+        Some(Goal::EatFood(n)) => parts.push(format!("Food: {}/{n}", game.food_eaten())),
+        Some(Goal::ReachLength(n)) => {
+            parts.push(format!("Length: {}/{n}", game.snake_iter().count()))
+        }
+        Some(Goal::Score(n)) => parts.push(format!("Goal: {}/{n}", game.score())),
+        Some(Goal::Survive(_) | Goal::TimeAttack(_)) => (),
+    }
+    if let Some(left) = game.time_left() {
+        let secs = left.as_secs_f64().ceil() as u64;
+        parts.push(format!("Time: {}:{:02}", secs / 60, secs % 60));
+    }
+    if game.conf().rivals > 0 {
+        parts.push(format!("Rivals: {}", game.rivals().len()));
+    }
+    if game.conf().lives > 1 {
+        parts.push(format!("Lives: {}", game.lives_left()));
+    }
+    match game.conf().on_self_hit {
+        SelfHit::Die => (),
+        SelfHit::Cut => parts.push("Cut".to_owned()),
+        SelfHit::Pass => parts.push("Ghost".to_owned()),
+    }
+
+    let mut text = parts.join("  ");
+    while text.width() > columns && parts.len() > 1 {
+        parts.pop();
+        text = parts.join("  ");
+    }
+    if text.width() > columns {
+        text = game.score().to_string();
+    }
+    text.truncate(columns);
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game::{DeathCause, FoodPlacement, GameConf},
+        grid::Grid,
+        themes::{BoardBuiltin, FoodBuiltin, SnakeBuiltin},
+    };
+    use clap::ValueEnum;
+
+    fn theme(board: BoardBuiltin, snake: SnakeBuiltin, food: FoodBuiltin) -> FullTheme {
+        FullTheme {
+            board: board.into(),
+            snake: snake.into(),
+            food: food.into(),
+            display_score: true,
+        }
+    }
+
+    /// Field without walls, so the snakes wrap around the edges
+    fn conf(width: usize, height: usize) -> GameConf {
+        GameConf {
+            width,
+            height,
+            seed: 42,
+            solid_walls: false,
+            food_placement: FoodPlacement::Uniform,
+            ..crate::game::test_conf()
+        }
+    }
+
+    fn game(width: usize, height: usize, turns: &[Option<Dir>]) -> GameState {
+        play(conf(width, height), turns)
+    }
+
+    fn play(conf: GameConf, turns: &[Option<Dir>]) -> GameState {
+        let mut game = GameState::new(conf);
+        for turn in turns {
+            game.make_step(*turn);
+        }
+        game
+    }
+
+    fn draw(game: &GameState, theme: &FullTheme) -> String {
+        let (columns, rows) = Layout::new(theme).screen_size(game.width(), game.height());
+        let mut renderer =
+            Renderer::new(Grid::new(columns, rows), game.width(), game.height(), theme);
+        renderer.render_game(game, theme).unwrap();
+        renderer.into_backend().to_string()
+    }
+
+    fn assert_draws(game: &GameState, theme: &FullTheme, expected: &[&str]) {
+        let drawn = draw(game, theme);
+        let drawn: Vec<&str> = drawn.lines().collect();
+        assert_eq!(drawn, expected);
+    }
+
+    #[test]
+    fn builtin_themes_are_valid() {
+        for board in BoardBuiltin::value_variants() {
+            for snake in SnakeBuiltin::value_variants() {
+                for food in FoodBuiltin::value_variants() {
+                    let t = theme(board.clone(), snake.clone(), food.clone());
+                    let bad = t.validate();
+                    assert!(bad.is_empty(), "{board:?} {snake:?} {food:?}: {bad:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bent_snake() {
+        // the snake turns down and wraps to the bottom of the field
+        let g = game(5, 4, &[None, Some(Dir::Down), None, Some(Dir::Left)]);
+
+        let t = theme(
+            BoardBuiltin::Rounded,
+            SnakeBuiltin::Braille,
+            FoodBuiltin::Ascii,
+        );
+        #[rustfmt::skip]
+        assert_draws(&g, &t, &[
+            "╭─Score: 0─╮",
+            "│      ⠰⠶⠾⠇│",
+            "│        <3│",
+            "│          │",
+            "│        ⢰⡀│",
+            "╰──────────╯",
+        ]);
+
+        let t = theme(
+            BoardBuiltin::Rounded,
+            SnakeBuiltin::Line,
+            FoodBuiltin::Ascii,
+        );
+        #[rustfmt::skip]
+        assert_draws(&g, &t, &[
+            "╭─Score: 0─╮",
+            "│       ━┛ │",
+            "│        <3│",
+            "│          │",
+            "│        ╻ │",
+            "╰──────────╯",
+        ]);
+    }
+
+    #[test]
+    fn board_themes() {
+        let g = game(4, 3, &[]);
+        let t = |board| theme(board, SnakeBuiltin::Basic, FoodBuiltin::Ascii);
+
+        #[rustfmt::skip]
+        assert_draws(&g, &t(BoardBuiltin::Double), &[
+            "╔═0══════╗",
+            "║<>      ║",
+            "║[][][]  ║",
+            "║        ║",
+            "╚════════╝",
+        ]);
+        #[rustfmt::skip]
+        assert_draws(&g, &t(BoardBuiltin::Rounded), &[
+            "╭─0──────╮",
+            "│<>      │",
+            "│[][][]  │",
+            "│        │",
+            "╰────────╯",
+        ]);
+        #[rustfmt::skip]
+        assert_draws(&g, &t(BoardBuiltin::Ascii), &[
+            "*-0------*",
+            "|<>      |",
+            "|[][][]  |",
+            "|        |",
+            "*--------*",
+        ]);
+        #[rustfmt::skip]
+        assert_draws(&g, &t(BoardBuiltin::Classic), &[
+            "Score: 0",
+            "<>` ` `",
+            "[][][]`",
+            "` ` ` `",
+        ]);
+        #[rustfmt::skip]
+        assert_draws(&g, &t(BoardBuiltin::Empty), &[
+            "Score: 0",
+            "<>",
+            "[][][]",
+            "",
+        ]);
+        #[rustfmt::skip]
+        assert_draws(&g, &t(BoardBuiltin::Retro), &[
+            "Score: 0",
+            "<>░░░░░░",
+            "[][][]░░",
+            "░░░░░░░░",
+        ]);
+    }
+
+    #[test]
+    fn snake_themes() {
+        // the snake turns down, in front of the food
+        let conf = GameConf {
+            initial_length: 4,
+            ..conf(6, 3)
+        };
+        let g = play(conf, &[Some(Dir::Down)]);
+
+        for snake in SnakeBuiltin::value_variants() {
+            let t = theme(BoardBuiltin::Ascii, snake.clone(), FoodBuiltin::Ascii);
+            #[rustfmt::skip]
+            let expected: &[&str] = match snake {
+                SnakeBuiltin::Braille => &[
+                    "*-Score: 0---*",
+                    "|            |",
+                    "|    ⠠⠴⠶⠶⢶⡆69|",
+                    "|        ⠸⠇  |",
+                    "*------------*",
+                ],
+                SnakeBuiltin::Line => &[
+                    "*-Score: 0---*",
+                    "|            |",
+                    "|     ━━━┓ 69|",
+                    "|        ╹   |",
+                    "*------------*",
+                ],
+                SnakeBuiltin::Retro => &[
+                    "*-Score: 0---*",
+                    "|            |",
+                    "|    ██████69|",
+                    "|        ██  |",
+                    "*------------*",
+                ],
+                SnakeBuiltin::Basic => &[
+                    "*-Score: 0---*",
+                    "|            |",
+                    "|    [][][]69|",
+                    "|        []  |",
+                    "*------------*",
+                ],
+            };
+            assert_draws(&g, &t, expected);
+        }
+    }
+
+    #[test]
+    fn food_themes() {
+        // each food of the field gets its own glyph
+        let g = play(
+            GameConf {
+                food_n: 3,
+                ..conf(5, 3)
+            },
+            &[],
+        );
+
+        for food in FoodBuiltin::value_variants() {
+            let t = theme(BoardBuiltin::Ascii, SnakeBuiltin::Basic, food.clone());
+            let [a, b, c] = match food {
+                FoodBuiltin::Emoji => ["🌽", "🍋", "🥝"],
+                FoodBuiltin::Ascii => ["<>", "69", "$$"],
+                FoodBuiltin::Star => ["★ ", "★ ", "★ "],
+                FoodBuiltin::Armenian => ["դ ", "ք ", "պ "],
+                FoodBuiltin::Greek => ["ς ", "μ ", "ρ "],
+                FoodBuiltin::Retro => ["██", "██", "██"],
+                FoodBuiltin::Braille => ["⢾⡷", "⢎⡱", "⡱⢎"],
+                FoodBuiltin::Math => ["± ", "∃ ", "∆y"],
+                FoodBuiltin::Chess => ["♚ ", "♟ ", "♞ "],
+            };
+            #[rustfmt::skip]
+            assert_draws(&g, &t, &[
+                "*-Score: 0-*",
+                &format!("|{a}        |"),
+                &format!("|  [][][]{b}|"),
+                &format!("|  {c}      |"),
+                "*----------*",
+            ]);
+        }
+    }
+
+    #[test]
+    fn snake_wrapping_across_edges() {
+        let t = theme(BoardBuiltin::Ascii, SnakeBuiltin::Line, FoodBuiltin::Ascii);
+
+        #[rustfmt::skip]
+        assert_draws(&game(5, 3, &[None, None, None]), &t, &[
+            "*-Score: 1-*",
+            "|          |",
+            "|━━━    ━━━|",
+            "|  $$      |",
+            "*----------*",
+        ]);
+        #[rustfmt::skip]
+        assert_draws(&game(5, 3, &[Some(Dir::Up), None]), &t, &[
+            "*-Score: 0-*",
+            "|      ┃   |",
+            "|      ╹ 69|",
+            "|      ╻   |",
+            "*----------*",
+        ]);
+    }
+
+    #[test]
+    fn snake_through_portal() {
+        let t = theme(BoardBuiltin::Ascii, SnakeBuiltin::Line, FoodBuiltin::Ascii);
+        let conf = || GameConf {
+            portals: 1,
+            seed: 9,
+            ..conf(6, 4)
+        };
+
+        // the snake enters the portal in front of it, and comes out of the one below
+        #[rustfmt::skip]
+        assert_draws(&play(conf(), &[None]), &t, &[
+            "*-Score: 0---*",
+            "|            |",
+            "|            |",
+            "|<3   ━━━@@  |",
+            "|  ━         |",
+            "*------------*",
+        ]);
+        #[rustfmt::skip]
+        assert_draws(&play(conf(), &[None, None]), &t, &[
+            "*-Score: 0---*",
+            "|            |",
+            "|            |",
+            "|<3     ━@@  |",
+            "|  ━━━       |",
+            "*------------*",
+        ]);
+    }
+
+    #[test]
+    fn self_hit_rules() {
+        let t = theme(BoardBuiltin::Ascii, SnakeBuiltin::Line, FoodBuiltin::Ascii);
+        // the head goes into the second body segment
+        let turns = [Some(Dir::Down), Some(Dir::Left), Some(Dir::Up)];
+        let conf = |on_self_hit| GameConf {
+            initial_length: 5,
+            on_self_hit,
+            ..conf(9, 4)
+        };
+
+        // the snake loses the hit segment and everything behind it
+        #[rustfmt::skip]
+        assert_draws(&play(conf(SelfHit::Cut), &turns), &t, &[
+            "*-Score: 0  Cut----*",
+            "|                  |",
+            "|              69  |",
+            "|          ╻ ╻     |",
+            "|          ┗━┛     |",
+            "*------------------*",
+        ]);
+        // the head is drawn on top of the tail it passes through
+        #[rustfmt::skip]
+        assert_draws(&play(conf(SelfHit::Pass), &turns), &t, &[
+            "*-Score: 0  Ghost--*",
+            "|                  |",
+            "|              69  |",
+            "|          ╻ ┓     |",
+            "|          ┗━┛     |",
+            "*------------------*",
+        ]);
+    }
+
+    #[test]
+    fn fatal_cell() {
+        let t = theme(BoardBuiltin::Ascii, SnakeBuiltin::Line, FoodBuiltin::Ascii);
+        let turns = [Some(Dir::Down), Some(Dir::Left), Some(Dir::Up)];
+        let conf = GameConf {
+            initial_length: 5,
+            ..conf(9, 4)
+        };
+        let game = play(conf, &turns);
+        assert_eq!(
+            game.last_death().map(|d| (d.cause, d.tick)),
+            Some((
+                DeathCause::OwnBody {
+                    pos: Coords { x: 5, y: 2 },
+                    segment: 3
+                },
+                3
+            ))
+        );
+
+        // the snake stays where it was, with the hit segment marked
+        #[rustfmt::skip]
+        assert_draws(&game, &t, &[
+            "*-Score: 0---------*",
+            "|                  |",
+            "|              69  |",
+            "|         ━XX┓     |",
+            "|          ╻ ┛     |",
+            "*------------------*",
+        ]);
+    }
+
+    #[test]
+    fn rivals() {
+        let t = theme(BoardBuiltin::Ascii, SnakeBuiltin::Basic, FoodBuiltin::Ascii);
+        let conf = GameConf {
+            rivals: 1,
+            ..conf(10, 5)
+        };
+        let game = play(conf, &[None; 2]);
+
+        // the rival wraps around the field, drawn with its own glyphs
+        #[rustfmt::skip]
+        assert_draws(&game, &t, &[
+            "*-Score: 1-----------*",
+            "|          $$        |",
+            "|                    |",
+            "|        [][][][]    |",
+            "|  OO                |",
+            "|oooo              oo|",
+            "*--------------------*",
+        ]);
+    }
+
+    #[test]
+    fn respawn() {
+        let t = theme(BoardBuiltin::Ascii, SnakeBuiltin::Line, FoodBuiltin::Ascii);
+        let conf = GameConf {
+            solid_walls: true,
+            lives: 2,
+            ..conf(16, 3)
+        };
+        let step = |n| play(conf.clone(), &vec![None; n]);
+
+        // the snake hits the wall and is placed back in the middle of the field
+        #[rustfmt::skip]
+        assert_draws(&step(8), &t, &[
+            "*-Score: 1  Ready: 3  Lives: 1---*",
+            "|                                |",
+            "|             ━━━━               |",
+            "|$$                              |",
+            "*--------------------------------*",
+        ]);
+        // and blinks during the countdown
+        #[rustfmt::skip]
+        assert_draws(&step(9), &t, &[
+            "*-Score: 1  Ready: 3  Lives: 1---*",
+            "|                                |",
+            "|                                |",
+            "|$$                              |",
+            "*--------------------------------*",
+        ]);
+        #[rustfmt::skip]
+        assert_draws(&step(8 + 3 * 6 + 1), &t, &[
+            "*-Score: 1  Lives: 1-------------*",
+            "|                                |",
+            "|               ━━━━             |",
+            "|$$                              |",
+            "*--------------------------------*",
+        ]);
+    }
+
+    #[test]
+    fn hint() {
+        let t = theme(BoardBuiltin::Ascii, SnakeBuiltin::Line, FoodBuiltin::Ascii);
+        let conf = GameConf {
+            solid_walls: true,
+            ..conf(16, 3)
+        };
+        let game = play(conf, &[None; 8]);
+        let draw = |hint| {
+            let mut renderer = Renderer::new(Grid::new(34, 5), 16, 3, &t);
+            renderer.set_hint(Some(hint));
+            renderer.render_game(&game, &t).unwrap();
+            renderer.into_backend().to_string()
+        };
+
+        // the hint is drawn over the middle row of the field
+        #[rustfmt::skip]
+        assert_eq!(draw("Press R to rewind, Q to quit").lines().collect::<Vec<_>>(), [
+            "*-Score: 1-----------------------*",
+            "|                                |",
+            "|  Press R to rewind, Q to quitXX|",
+            "|$$                              |",
+            "*--------------------------------*",
+        ]);
+        // or not at all, if it doesn't fit
+        assert!(!draw("Press R to rewind, or Q to quit it").contains("Press"));
+    }
+
+    #[test]
+    fn goal_progress() {
+        let t = theme(BoardBuiltin::Ascii, SnakeBuiltin::Line, FoodBuiltin::Ascii);
+        let with_goal = |goal| GameConf {
+            goal: Some(goal),
+            ..conf(12, 1)
+        };
+
+        let g = play(with_goal(Goal::TimeAttack(90)), &[None; 7]);
+        assert_eq!(g.status(), GameStatus::Ongoing);
+        #[rustfmt::skip]
+        assert_draws(&g, &t, &[
+            "*-Score: 1  Time: 1:29---*",
+            "|━━━ $$               ━━━|",
+            "*------------------------*",
+        ]);
+
+        let g = play(with_goal(Goal::Score(1)), &[None; 6]);
+        assert_eq!(g.status(), GameStatus::Win);
+        #[rustfmt::skip]
+        assert_draws(&g, &t, &[
+            "*-Score: 1  Goal: 1/1----*",
+            "|━   $$             ━━━━━|",
+            "*------------------------*",
+        ]);
+    }
+
+    #[test]
+    fn score_without_border() {
+        let t = theme(
+            BoardBuiltin::Classic,
+            SnakeBuiltin::Line,
+            FoodBuiltin::Ascii,
+        );
+
+        #[rustfmt::skip]
+        assert_draws(&game(5, 3, &[]), &t, &[
+            "Score: 0",
+            "` ` ` ` `",
+            "`  ━━━━ 69",
+            "` ` ` ` `",
+        ]);
+        // score must not be wider than the field
+        #[rustfmt::skip]
+        assert_draws(&game(3, 2, &[]), &t, &[
+            "0",
+            "` 69`",
+            " ━━━━",
+        ]);
+    }
+
+    #[test]
+    fn screen_too_small() {
+        let t = theme(
+            BoardBuiltin::Rounded,
+            SnakeBuiltin::Line,
+            FoodBuiltin::Ascii,
+        );
+        let g = game(5, 3, &[]);
+
+        let mut renderer = Renderer::new(Grid::new(25, 2), g.width(), g.height(), &t);
+        renderer.resize(25, 2).unwrap();
+        renderer.render_game(&g, &t).unwrap();
+        assert_eq!(
+            renderer.into_backend().to_string(),
+            "Terminal is too small\n\n"
+        );
+    }
+
+    #[test]
+    fn layout_fits_the_screen() {
+        for board in BoardBuiltin::value_variants() {
+            let t = theme(board.clone(), SnakeBuiltin::Line, FoodBuiltin::Ascii);
+            let layout = Layout::new(&t);
+            let (w, h) = layout.max_field_size(80, 24);
+            let (columns, rows) = layout.screen_size(w, h);
+            assert!((79..=80).contains(&columns), "{board:?}: {columns}");
+            assert_eq!(rows, 24, "{board:?}");
+        }
     }
 }
@@ -0,0 +1,126 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use eyre::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::game::{Dir, GameConf};
+
+/// Version of the replay file format, bumped on incompatible changes
+const VERSION: u32 = 1;
+
+/// Everything needed to reproduce a game: the game is fully
+/// determined by its config (including the seed) and the turns made.
+#[derive(Serialize, Deserialize)]
+pub struct Replay {
+    version: u32,
+    pub conf: GameConf,
+    /// Turns made during the game, with the tick on which they were made
+    pub turns: Vec<(u64, Dir)>,
+    /// Changes of the field size made to fit the terminal, with the tick before which
+    /// they were made
+    #[serde(default)]
+    pub resizes: Vec<(u64, usize, usize)>,
+    /// Number of ticks the game lasted, the game may have been quit before the end
+    pub ticks: u64,
+}
+
+impl Replay {
+    pub fn new(conf: GameConf) -> Self {
+        Self {
+            version: VERSION,
+            conf,
+            turns: Vec::new(),
+            resizes: Vec::new(),
+            ticks: 0,
+        }
+    }
+
+    pub fn record_turn(&mut self, tick: u64, dir: Dir) {
+        self.turns.push((tick, dir));
+    }
+
+    pub fn record_resize(&mut self, tick: u64, width: usize, height: usize) {
+        self.resizes.push((tick, width, height));
+    }
+
+    /// Forgets the turns and resizes made on `tick` and later, after the game was rewound to it
+    pub fn rewind_to(&mut self, tick: u64) {
+        self.turns.retain(|&(t, _)| t < tick);
+        self.resizes.retain(|&(t, _, _)| t < tick);
+    }
+
+    pub fn finish(&mut self, ticks: u64) {
+        self.ticks = ticks;
+    }
+
+    pub fn load(path: &Path) -> eyre::Result<Self> {
+        let file = File::open(path)
+            .wrap_err_with(|| format!("Unable to open replay file {}", path.display()))?;
+        let replay: Replay = serde_json::from_reader(std::io::BufReader::new(file))
+            .wrap_err("Unable to parse the replay file")?;
+
+        if replay.version != VERSION {
+            eyre::bail!(
+                "Replay file has version {}, but only version {VERSION} is supported",
+                replay.version
+            );
+        }
+
+        Ok(replay)
+    }
+
+    pub fn save(&self, path: &Path) -> eyre::Result<()> {
+        let file = File::create(path)
+            .wrap_err_with(|| format!("Unable to create replay file {}", path.display()))?;
+        serde_json::to_writer(BufWriter::new(file), self).wrap_err("Unable to write the replay")?;
+        Ok(())
+    }
+
+    pub fn player(&self) -> ReplayPlayer<'_> {
+        ReplayPlayer {
+            turns: &self.turns,
+            resizes: &self.resizes,
+        }
+    }
+}
+
+/// Gives out turns and resizes of a replay tick by tick
+pub struct ReplayPlayer<'a> {
+    turns: &'a [(u64, Dir)],
+    resizes: &'a [(u64, usize, usize)],
+}
+
+impl ReplayPlayer<'_> {
+    /// Turn to make on `tick`. Ticks must be asked for in increasing order.
+    pub fn turn_at(&mut self, tick: u64) -> Option<Dir> {
+        // skip turns that have been missed
+        while let [(t, _), rest @ ..] = self.turns {
+            if *t >= tick {
+                break;
+            }
+            self.turns = rest;
+        }
+
+        match self.turns {
+            [(t, dir), rest @ ..] if *t == tick => {
+                self.turns = rest;
+                Some(*dir)
+            }
+            _ => None,
+        }
+    }
+
+    /// Size the field is resized to before the step on `tick`, if it is.
+    /// Ticks must be asked for in increasing order.
+    pub fn resize_at(&mut self, tick: u64) -> Option<(usize, usize)> {
+        let mut size = None;
+        while let [(t, w, h), rest @ ..] = self.resizes {
+            if *t > tick {
+                break;
+            }
+            size = Some((*w, *h));
+            self.resizes = rest;
+        }
+        size
+    }
+}
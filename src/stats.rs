@@ -0,0 +1,293 @@
+//! Lifetime statistics of the player, summed up over all games played on this machine
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use eyre::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::{GameState, GameStatus},
+    storage,
+};
+
+/// Number of the latest scores kept for the sparklines
+const RECENT_SCORES: usize = 40;
+/// Number of the latest scores kept for every game config
+const RECENT_CONFIG_SCORES: usize = 20;
+
+/// Names of the built-in themes the game was played with
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ThemeNames {
+    pub snake: String,
+    pub board: String,
+    pub food: String,
+}
+
+/// Stats of the games played with one game config
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConfigStats {
+    pub games: u64,
+    pub total_score: u64,
+    /// Game time in seconds
+    pub total_time: f64,
+    pub total_length: u64,
+    /// The latest scores, oldest first
+    pub recent_scores: Vec<u32>,
+}
+
+impl ConfigStats {
+    pub fn mean_score(&self) -> f64 {
+        self.mean(self.total_score as f64)
+    }
+
+    pub fn mean_time(&self) -> f64 {
+        self.mean(self.total_time)
+    }
+
+    pub fn mean_length(&self) -> f64 {
+        self.mean(self.total_length as f64)
+    }
+
+    fn mean(&self, total: f64) -> f64 {
+        match self.games {
+            0 => 0.0,
+            n => total / n as f64,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    pub games: u64,
+    pub food_eaten: u64,
+    /// Game time in seconds
+    pub time: f64,
+    pub longest_snake: usize,
+    /// Number of games lost to every cause, see [`crate::game::DeathCause::kind`]
+    pub deaths: BTreeMap<String, u64>,
+    /// Number of games played with every theme
+    pub snake_themes: BTreeMap<String, u64>,
+    pub board_themes: BTreeMap<String, u64>,
+    pub food_themes: BTreeMap<String, u64>,
+    /// Stats of every game config, by its [`crate::game::GameConf::score_key`]
+    pub configs: BTreeMap<String, ConfigStats>,
+    /// The latest scores of all configs, oldest first
+    pub recent_scores: Vec<u32>,
+}
+
+impl Stats {
+    /// Default location of the stats file
+    pub fn default_path() -> Option<PathBuf> {
+        Some(dirs::data_dir()?.join("snekgame").join("stats.json"))
+    }
+
+    /// Loads the stats, if the file doesn't exist yet no games were played
+    pub fn load(path: &Path) -> eyre::Result<Self> {
+        storage::load_json(path).wrap_err("Unable to load stats")
+    }
+
+    pub fn save(&self, path: &Path) -> eyre::Result<()> {
+        storage::save_json(path, self)
+    }
+
+    /// Adds the finished (or quit) game to the stats
+    pub fn add(&mut self, game: &GameState, themes: &ThemeNames) {
+        let length = game.snake_iter().count();
+        let time = game.elapsed().as_secs_f64();
+
+        self.games += 1;
+        self.food_eaten += u64::from(game.food_eaten());
+        self.time += time;
+        self.longest_snake = self.longest_snake.max(length);
+        if let (GameStatus::Dead, Some(death)) = (game.status(), game.last_death()) {
+            *self
+                .deaths
+                .entry(death.cause.kind().to_string())
+                .or_default() += 1;
+        }
+
+        *self.snake_themes.entry(themes.snake.clone()).or_default() += 1;
+        *self.board_themes.entry(themes.board.clone()).or_default() += 1;
+        *self.food_themes.entry(themes.food.clone()).or_default() += 1;
+
+        let config = self.configs.entry(game.conf().score_key()).or_default();
+        config.games += 1;
+        config.total_score += u64::from(game.score());
+        config.total_time += time;
+        config.total_length += length as u64;
+        push_recent(
+            &mut config.recent_scores,
+            game.score(),
+            RECENT_CONFIG_SCORES,
+        );
+
+        push_recent(&mut self.recent_scores, game.score(), RECENT_SCORES);
+    }
+}
+
+fn push_recent(scores: &mut Vec<u32>, score: u32, max: usize) {
+    scores.push(score);
+    if scores.len() > max {
+        scores.drain(..scores.len() - max);
+    }
+}
+
+/// Adds the game to the stats file. Games quit before the first step are not counted.
+/// If the stats can't be loaded or saved, the player is warned and nothing else happens.
+pub fn record_game(game: &GameState, themes: &ThemeNames) {
+    if let Err(e) = try_record_game(game, themes) {
+        eprintln!("Unable to save stats: {e:#}");
+    }
+}
+
+fn try_record_game(game: &GameState, themes: &ThemeNames) -> eyre::Result<()> {
+    let Some(path) = Stats::default_path() else {
+        return Ok(());
+    };
+    if game.tick() == 0 {
+        return Ok(());
+    }
+
+    let mut stats = Stats::load(&path)?;
+    stats.add(game, themes);
+    stats.save(&path)
+}
+
+/// Draws the values as a line of bars, the highest value gets the full bar
+pub fn sparkline(values: &[u32]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    let max = values.iter().copied().max().unwrap_or(0);
+    values
+        .iter()
+        .map(|&v| match max {
+            0 => BARS[0],
+            max => BARS[(v as usize * (BARS.len() - 1)).div_ceil(max as usize)],
+        })
+        .collect()
+}
+
+/// Formats seconds as e.g. `1h 02m 13s`
+fn format_time(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    match () {
+        _ if h > 0 => format!("{h}h {m:02}m {s:02}s"),
+        _ if m > 0 => format!("{m}m {s:02}s"),
+        _ => format!("{s}s"),
+    }
+}
+
+/// The most used name, the first one in the order of names on a tie
+fn favorite(counts: &BTreeMap<String, u64>) -> &str {
+    counts
+        .iter()
+        .rev()
+        .max_by_key(|&(_, n)| n)
+        .map_or("-", |(name, _)| name.as_str())
+}
+
+/// Prints the stats of the `stats` command
+pub fn print(stats: &Stats) {
+    if stats.games == 0 {
+        println!("No games played yet");
+        return;
+    }
+
+    let deaths: Vec<String> = stats
+        .deaths
+        .iter()
+        .map(|(cause, n)| format!("{cause} {n}"))
+        .collect();
+
+    println!("Games played     {}", stats.games);
+    println!("Food eaten       {}", stats.food_eaten);
+    println!("Time played      {}", format_time(stats.time));
+    println!("Longest snake    {}", stats.longest_snake);
+    println!(
+        "Deaths           {}",
+        match deaths.is_empty() {
+            true => "-".to_string(),
+            false => deaths.join(", "),
+        }
+    );
+    println!(
+        "Favorite themes  snake {}, board {}, food {}",
+        favorite(&stats.snake_themes),
+        favorite(&stats.board_themes),
+        favorite(&stats.food_themes),
+    );
+    println!(
+        "Recent scores    {} best {}",
+        sparkline(&stats.recent_scores),
+        stats.recent_scores.iter().max().unwrap_or(&0),
+    );
+    println!();
+
+    println!(
+        "{:>6}  {:>10}  {:>9}  {:>11}  {:<w$}  Config",
+        "Games",
+        "Mean score",
+        "Mean time",
+        "Mean length",
+        "Recent",
+        w = RECENT_CONFIG_SCORES,
+    );
+    let mut configs: Vec<_> = stats.configs.iter().collect();
+    configs.sort_by_key(|(_, c)| std::cmp::Reverse(c.games));
+    for (key, c) in configs {
+        println!(
+            "{:>6}  {:>10.1}  {:>9}  {:>11.1}  {:<w$}  {key}",
+            c.games,
+            c.mean_score(),
+            format_time(c.mean_time()),
+            c.mean_length(),
+            sparkline(&c.recent_scores),
+            w = RECENT_CONFIG_SCORES,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sparklines() {
+        assert_eq!(sparkline(&[]), "");
+        assert_eq!(sparkline(&[0, 0]), "▁▁");
+        assert_eq!(sparkline(&[0, 1, 7, 14]), "▁▂▅█");
+    }
+
+    #[test]
+    fn games_add_up() {
+        let themes = ThemeNames {
+            snake: "fire".to_string(),
+            board: "classic".to_string(),
+            food: "ascii".to_string(),
+        };
+        let mut game = GameState::new(crate::game::test_conf());
+        while game.status() != GameStatus::Dead {
+            game.make_step(None);
+        }
+
+        let mut stats = Stats::default();
+        for _ in 0..RECENT_SCORES + 1 {
+            stats.add(&game, &themes);
+        }
+        let n = RECENT_SCORES as u64 + 1;
+        assert_eq!(stats.games, n);
+        assert_eq!(stats.deaths.values().sum::<u64>(), n);
+        assert_eq!(stats.longest_snake, game.snake_iter().count());
+        assert_eq!(stats.recent_scores.len(), RECENT_SCORES);
+        assert_eq!(favorite(&stats.snake_themes), "fire");
+
+        let config = &stats.configs[&game.conf().score_key()];
+        assert_eq!(config.games, n);
+        assert_eq!(config.recent_scores.len(), RECENT_CONFIG_SCORES);
+        assert_eq!(config.mean_length(), game.snake_iter().count() as f64);
+    }
+}
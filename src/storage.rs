@@ -0,0 +1,65 @@
+//! Files the game keeps between the runs, like the high scores
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use eyre::Context;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Reads JSON from `path`. If the file doesn't exist yet, the default value is returned.
+pub fn load_json<T: DeserializeOwned + Default>(path: &Path) -> eyre::Result<T> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(T::default()),
+        Err(e) => return Err(e).wrap_err_with(|| format!("Unable to open {}", path.display())),
+    };
+
+    serde_json::from_reader(BufReader::new(file))
+        .wrap_err_with(|| format!("Unable to parse {}", path.display()))
+}
+
+/// Writes `value` as JSON to `path`. It's written to a temporary file first, which then
+/// replaces the old one, so an interrupted write never leaves a broken file behind.
+pub fn save_json(path: &Path, value: &impl Serialize) -> eyre::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .wrap_err_with(|| format!("Unable to create directory {}", dir.display()))?;
+    }
+
+    let tmp = path.with_extension("json.tmp");
+    let file =
+        File::create(&tmp).wrap_err_with(|| format!("Unable to create {}", tmp.display()))?;
+    let mut out = BufWriter::new(file);
+    serde_json::to_writer_pretty(&mut out, value)
+        .and_then(|()| out.flush().map_err(serde_json::Error::io))
+        .wrap_err_with(|| format!("Unable to write {}", tmp.display()))?;
+    out.get_ref()
+        .sync_all()
+        .wrap_err_with(|| format!("Unable to write {}", tmp.display()))?;
+
+    std::fs::rename(&tmp, path).wrap_err_with(|| format!("Unable to replace {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_the_file() {
+        let dir = std::env::temp_dir().join(format!("snekgame-storage-{}", std::process::id()));
+        let path = dir.join("test.json");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, "{ broken").unwrap();
+
+        assert!(load_json::<Vec<u32>>(&path).is_err());
+        save_json(&path, &vec![1, 2, 3]).unwrap();
+        assert_eq!(load_json::<Vec<u32>>(&path).unwrap(), [1, 2, 3]);
+        assert!(!path.with_extension("json.tmp").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(load_json::<Vec<u32>>(&path).unwrap().is_empty());
+    }
+}
@@ -1,5 +1,7 @@
 // TODO: custom themes
 
+use crate::game::{Owner, PLAYER};
+
 pub struct FullTheme {
     pub food: FoodTheme,
     pub board: BoardTheme,
@@ -7,6 +9,78 @@ pub struct FullTheme {
     pub display_score: bool,
 }
 
+impl FullTheme {
+    /// Checks that every glyph of the theme takes the right amount of terminal columns.
+    /// Every cell must be exactly two columns wide, or the board gets skewed.
+    pub fn validate(&self) -> Vec<BadGlyph> {
+        let mut bad = Vec::new();
+        let mut check = |part: &'static str, glyph: &str, expected: usize| {
+            let width = glyph.width();
+            if width != expected {
+                bad.push(BadGlyph {
+                    part,
+                    glyph: glyph.to_owned(),
+                    width,
+                    expected,
+                });
+            }
+        };
+
+        for glyph in &self.food.theme {
+            check("food", glyph, CELL_WIDTH);
+        }
+
+        check("board empty", &self.board.empty, CELL_WIDTH);
+        check("board portal", &self.board.portal, CELL_WIDTH);
+        check("board obstacle", &self.board.obstacle, CELL_WIDTH);
+        check("board food trail", &self.board.food_trail, CELL_WIDTH);
+        check("board fatal", &self.board.fatal, CELL_WIDTH);
+        if let Some(b) = &self.board.border {
+            check("border horizontal", &b.horizontal, CELL_WIDTH);
+            // corners must line up with the vertical parts of the border
+            let vertical = b.vertical.width();
+            check("border top left", &b.top_left, vertical);
+            check("border bottom left", &b.bottom_left, vertical);
+            check("border top right", &b.top_right, vertical);
+            check("border bottom right", &b.bottom_right, vertical);
+        }
+
+        let s = &self.snake;
+        for (part, glyph) in [
+            ("snake head up", &s.head_up),
+            ("snake head down", &s.head_down),
+            ("snake head left", &s.head_left),
+            ("snake head right", &s.head_right),
+            ("snake tail up", &s.tail_up),
+            ("snake tail down", &s.tail_down),
+            ("snake tail left", &s.tail_left),
+            ("snake tail right", &s.tail_right),
+            ("snake body vertical", &s.body_vertical),
+            ("snake body horizontal", &s.body_horizontal),
+            ("snake body up right", &s.body_up_right),
+            ("snake body down right", &s.body_down_right),
+            ("snake body up left", &s.body_up_left),
+            ("snake body down left", &s.body_down_left),
+            ("rival head", &s.rival_head),
+            ("rival body", &s.rival_body),
+        ] {
+            check(part, glyph, CELL_WIDTH);
+        }
+
+        bad
+    }
+}
+
+/// Glyph of a theme that has the wrong width
+#[derive(Debug, thiserror::Error)]
+#[error("{part} glyph {glyph:?} is {width} columns wide instead of {expected}")]
+pub struct BadGlyph {
+    pub part: &'static str,
+    pub glyph: String,
+    pub width: usize,
+    pub expected: usize,
+}
+
 /// Constructs [`Vec<Cow<'static, str>>`]
 macro_rules! cow_vec {
     [] => { Vec::new() };
@@ -15,9 +89,13 @@ macro_rules! cow_vec {
     };
 }
 
-use std::{borrow::Cow, io::Write};
+use std::borrow::Cow;
 
-#[derive(Clone, Debug, clap::ValueEnum)]
+use unicode_width::UnicodeWidthStr;
+
+use crate::render::CELL_WIDTH;
+
+#[derive(Clone, Debug, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
 pub enum FoodBuiltin {
     Emoji,
     Ascii,
@@ -96,25 +174,17 @@ impl From<FoodBuiltin> for FoodTheme {
 }
 
 impl FoodTheme {
-    pub fn display_with_id(
-        &self,
-        mut f: impl Write,
-        id: usize,
-        color: bool,
-    ) -> std::io::Result<()> {
+    /// Glyph and color of the food with `id`
+    pub fn glyph_with_id(&self, id: usize) -> (&str, Option<crossterm::style::Color>) {
         let food_id = id & 0x0000FFFF; // mask off lower 16 bits
         let food = self.theme[food_id % self.theme.len()].as_ref();
 
-        if color && !self.colors.is_empty() {
-            let color_id = id >> 16; // mask off upper 16 bits
-            let color = self.colors[color_id % self.colors.len()];
-
-            write!(f, "{}", crossterm::style::Stylize::with(food, color))?;
-        } else {
-            write!(f, "{}", food)?;
+        if self.colors.is_empty() {
+            return (food, None);
         }
 
-        Ok(())
+        let color_id = id >> 16; // mask off upper 16 bits
+        (food, Some(self.colors[color_id % self.colors.len()]))
     }
 }
 
@@ -130,9 +200,29 @@ pub struct BorderTheme {
 pub struct BoardTheme {
     pub border: Option<BorderTheme>,
     pub empty: Cow<'static, str>,
+    pub portal: Cow<'static, str>,
+    /// Both portals of a pair have the same color, so they can be told apart from other pairs
+    pub portal_colors: Vec<crossterm::style::Color>,
+    pub obstacle: Cow<'static, str>,
+    /// Drawn where moving food was before its last move
+    pub food_trail: Cow<'static, str>,
+    /// Drawn where the snake died, after the game is over
+    pub fatal: Cow<'static, str>,
+    pub fatal_color: Option<crossterm::style::Color>,
 }
 
-#[derive(Clone, Debug, clap::ValueEnum)]
+impl BoardTheme {
+    /// Glyph and color of the portal pair with index `i`
+    pub fn portal_with_index(&self, i: usize) -> (&str, Option<crossterm::style::Color>) {
+        let color = match self.portal_colors.len() {
+            0 => None,
+            n => Some(self.portal_colors[i % n]),
+        };
+        (&self.portal, color)
+    }
+}
+
+#[derive(Clone, Debug, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
 pub enum BoardBuiltin {
     Double,
     Rounded,
@@ -142,6 +232,11 @@ pub enum BoardBuiltin {
     Retro,
 }
 
+const PORTAL_COLORS: [crossterm::style::Color; 6] = {
+    use crossterm::style::Color as C;
+    [C::Magenta, C::Cyan, C::Yellow, C::Green, C::Blue, C::Red]
+};
+
 impl From<BoardBuiltin> for BoardTheme {
     fn from(builtin: BoardBuiltin) -> Self {
         match builtin {
@@ -155,6 +250,12 @@ impl From<BoardBuiltin> for BoardTheme {
                     bottom_right: "╝".into(),
                 }),
                 empty: "  ".into(),
+                portal: "◎ ".into(),
+                portal_colors: PORTAL_COLORS.to_vec(),
+                obstacle: "██".into(),
+                food_trail: "· ".into(),
+                fatal: "✖ ".into(),
+                fatal_color: Some(crossterm::style::Color::Red),
             },
             BoardBuiltin::Rounded => BoardTheme {
                 border: Some(BorderTheme {
@@ -166,6 +267,12 @@ impl From<BoardBuiltin> for BoardTheme {
                     bottom_right: "╯".into(),
                 }),
                 empty: "  ".into(),
+                portal: "◖◗".into(),
+                portal_colors: PORTAL_COLORS.to_vec(),
+                obstacle: "██".into(),
+                food_trail: "· ".into(),
+                fatal: "✖ ".into(),
+                fatal_color: Some(crossterm::style::Color::Red),
             },
             BoardBuiltin::Ascii => BoardTheme {
                 border: Some(BorderTheme {
@@ -177,18 +284,42 @@ impl From<BoardBuiltin> for BoardTheme {
                     bottom_right: "*".into(),
                 }),
                 empty: "  ".into(),
+                portal: "@@".into(),
+                portal_colors: PORTAL_COLORS.to_vec(),
+                obstacle: "##".into(),
+                food_trail: "· ".into(),
+                fatal: "XX".into(),
+                fatal_color: Some(crossterm::style::Color::Red),
             },
             BoardBuiltin::Classic => BoardTheme {
                 border: None,
                 empty: "` ".into(),
+                portal: "@ ".into(),
+                portal_colors: PORTAL_COLORS.to_vec(),
+                obstacle: "# ".into(),
+                food_trail: ". ".into(),
+                fatal: "X ".into(),
+                fatal_color: Some(crossterm::style::Color::Red),
             },
             BoardBuiltin::Empty => BoardTheme {
                 border: None,
                 empty: "  ".into(),
+                portal: "◖◗".into(),
+                portal_colors: PORTAL_COLORS.to_vec(),
+                obstacle: "██".into(),
+                food_trail: "· ".into(),
+                fatal: "✖ ".into(),
+                fatal_color: Some(crossterm::style::Color::Red),
             },
             BoardBuiltin::Retro => BoardTheme {
                 border: None,
                 empty: "░░".into(),
+                portal: "▒▒".into(),
+                portal_colors: PORTAL_COLORS.to_vec(),
+                obstacle: "▓▓".into(),
+                food_trail: "▒░".into(),
+                fatal: "XX".into(),
+                fatal_color: Some(crossterm::style::Color::Red),
             },
         }
     }
@@ -212,9 +343,26 @@ pub struct SnakeTheme {
     pub body_down_right: Cow<'static, str>,
     pub body_up_left: Cow<'static, str>,
     pub body_down_left: Cow<'static, str>,
+
+    /// Rival snakes are drawn with their own glyphs, so they can be told apart from the
+    /// player without colors
+    pub rival_head: Cow<'static, str>,
+    pub rival_body: Cow<'static, str>,
+    /// Colors of the rival snakes
+    pub rival_colors: Vec<crossterm::style::Color>,
+}
+
+impl SnakeTheme {
+    /// Color of the snake of `owner`, the player's snake has the default color
+    pub fn color(&self, owner: Owner) -> Option<crossterm::style::Color> {
+        match (owner, self.rival_colors.len()) {
+            (PLAYER, _) | (_, 0) => None,
+            (rival, n) => Some(self.rival_colors[(rival - 1) % n]),
+        }
+    }
 }
 
-#[derive(Clone, Debug, clap::ValueEnum)]
+#[derive(Clone, Debug, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
 pub enum SnakeBuiltin {
     // TODO: more built-in snake themes
     Braille,
@@ -223,6 +371,11 @@ pub enum SnakeBuiltin {
     Basic,
 }
 
+const RIVAL_COLORS: [crossterm::style::Color; 5] = {
+    use crossterm::style::Color as C;
+    [C::Red, C::Blue, C::Yellow, C::Magenta, C::Cyan]
+};
+
 impl From<SnakeBuiltin> for SnakeTheme {
     fn from(builtin: SnakeBuiltin) -> Self {
         match builtin {
@@ -241,6 +394,9 @@ impl From<SnakeBuiltin> for SnakeTheme {
                 body_down_right: "⠾⠇".into(),
                 body_up_left: "⢰⡶".into(),
                 body_down_left: "⠸⠷".into(),
+                rival_head: "⣴⣦".into(),
+                rival_body: "⠛⠛".into(),
+                rival_colors: RIVAL_COLORS.to_vec(),
             },
             SnakeBuiltin::Line => SnakeTheme {
                 head_up: "╻ ".into(),
@@ -257,6 +413,9 @@ impl From<SnakeBuiltin> for SnakeTheme {
                 body_down_right: "┛ ".into(),
                 body_up_left: "┏━".into(),
                 body_down_left: "┗━".into(),
+                rival_head: "◉ ".into(),
+                rival_body: "○ ".into(),
+                rival_colors: RIVAL_COLORS.to_vec(),
             },
             SnakeBuiltin::Basic => SnakeTheme {
                 head_up: "[]".into(),
@@ -273,6 +432,9 @@ impl From<SnakeBuiltin> for SnakeTheme {
                 body_down_right: "[]".into(),
                 body_up_left: "[]".into(),
                 body_down_left: "[]".into(),
+                rival_head: "OO".into(),
+                rival_body: "oo".into(),
+                rival_colors: RIVAL_COLORS.to_vec(),
             },
             SnakeBuiltin::Retro => SnakeTheme {
                 head_up: "██".into(),
@@ -289,6 +451,9 @@ impl From<SnakeBuiltin> for SnakeTheme {
                 body_down_right: "██".into(),
                 body_up_left: "██".into(),
                 body_down_left: "██".into(),
+                rival_head: "▛▜".into(),
+                rival_body: "▚▞".into(),
+                rival_colors: RIVAL_COLORS.to_vec(),
             },
         }
     }